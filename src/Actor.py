@@ -1,4 +1,5 @@
 import collision
+import math
 import pyglet
 from pyglet.window import key
 from pyglet.math import Vec2
@@ -6,10 +7,11 @@ from pyglet.math import Vec2
 from Config import *
 from Event import *
 from HxPx import Hx, Px
+import Item
 
-DEFAULT_SPEED = 120
-DEFAULT_VERTICAL = 1.2
-DEFAULT_HEIGHT = 3
+TELEPORT_DISTANCE = TILE_WIDTH*2
+CORRECTION_DISTANCE = 2 # px, smaller corrections are snapped to
+CORRECTION_TIME = 0.1
 
 class State(quickle.Struct):
     id: int
@@ -23,6 +25,8 @@ class State(quickle.Struct):
     px: tuple
     typ: str
     busy: False
+    crouching: bool = False
+    mantle: float = 0
 
 class Impl(pyglet.event.EventDispatcher):
     def __init__(self, id, typ, px, behaviour):
@@ -35,12 +39,21 @@ class Impl(pyglet.event.EventDispatcher):
         self.air_time = None
         self.focus = Hx(0,0,0)
         self.height = DEFAULT_HEIGHT
-        self.speed = DEFAULT_SPEED
-        self.vertical = DEFAULT_VERTICAL
+        self.crouching = False
         self.px = Px(*(px[:3]))
         self.busy = False
+        self.mantle = 0
+        self.inventory = Item.Inventory()
+        self.equipment = Item.Equipment()
         self.collider = collision.Poly(collision.Vector(self.px.x, self.px.y), 
-                                       [collision.Vector(it.x, it.y) for it in Px(0,0,0).vertices(7, ORIENTATION_FLAT)], 0)
+                                       [collision.Vector(it.x, it.y) for it in Px(0,0,0).vertices(ACTOR_RADIUS, ORIENTATION_FLAT)], 0)
+
+    # what is worn is added on top of the defaults every time, so taking it off gives back exactly what was there
+    @property
+    def speed(self): return DEFAULT_SPEED + self.equipment.mod("speed")
+
+    @property
+    def vertical(self): return DEFAULT_VERTICAL + self.equipment.mod("vertical")
 
     @property
     def hx(self): return self._hx
@@ -54,12 +67,15 @@ class Impl(pyglet.event.EventDispatcher):
         self._hx = v.into_hx()
         self.recalc()
 
+    # rise toward vertical then fall at the same rate, shared by the server and client prediction
+    def airborne(self, actor, dt):
+        actor.air_time += dt
+        rise = actor.air_time*actor.speed/(TILE_RISE*2)
+        if actor.vertical <= 0 or rise/actor.vertical > 1: actor.air_dz -= actor.speed/(TILE_RISE*2)*dt
+        else: actor.air_dz = Vec2(0,0).lerp(Vec2(0,actor.vertical), rise/actor.vertical).y
+
     def update(self, actor, dt):
-        if actor.air_time is not None:
-            actor.air_time += dt
-            if (actor.air_time*actor.speed/(TILE_RISE*2))/actor.vertical > 1: actor.air_dz -= actor.speed/(TILE_RISE*2)*dt
-            else: actor.air_dz = Vec2(0,0).lerp(Vec2(0,actor.vertical), (actor.air_time*actor.speed/(TILE_RISE*2))/actor.vertical).y
-            self.dispatch_event('on_try', self.id, ActorMoveEvent(actor, dt), False)
+        if actor.air_time is not None: self.dispatch_event('on_try', None, ActorMoveEvent(actor, dt), False)
         
         elif self.behaviour is not None:
             self.behaviour.update(self, dt)
@@ -81,7 +97,9 @@ class Impl(pyglet.event.EventDispatcher):
                                   air_time=self.air_time, 
                                   px=self.px.state,
                                   typ=self.typ,
-                                  busy=self.busy)
+                                  busy=self.busy,
+                                  crouching=self.crouching,
+                                  mantle=self.mantle)
     
     @state.setter
     def state(self, v):
@@ -93,6 +111,8 @@ class Impl(pyglet.event.EventDispatcher):
         self.last_clock = v.last_clock
         self.typ = v.typ
         self.busy = v.busy
+        self.crouching = v.crouching
+        self.mantle = v.mantle
 
 Impl.register_event_type('on_try')
 Impl.register_event_type('on_looking_at')
@@ -102,8 +122,10 @@ class Actor(Impl):
         self.key_state = key_state_handler
         self.disp_dt = 0
         self.disp_pos = Px(0,0,0)
+        self.crouch_key = False
         sprite, anims = asset_factory.create_actor(typ)
         self.sprite = sprite
+        self.scale_y = sprite.scale_y
         self.animations = anims
         super().__init__(id, typ, px, None)
 
@@ -134,11 +156,9 @@ class Actor(Impl):
 
     def update(self, actor, dt):
         if actor.id != self.id: return
-        if actor.air_time is not None: 
-            actor.air_time += dt
-            if (actor.air_time*actor.speed/(TILE_RISE*2))/actor.vertical > 1: actor.air_dz -= actor.speed/(TILE_RISE*2)*dt
-            else: actor.air_dz = Vec2(0,0).lerp(Vec2(0,actor.vertical), (actor.air_time*actor.speed/(TILE_RISE*2))/actor.vertical).y
+        if actor.air_time is not None: self.airborne(actor, dt)
         else:
+            actor.crouching = self.key_state[key.C]
             if self.key_state[key.SPACE]: 
                 actor.air_time = 0
                 self.dispatch_event('on_try', actor.id, ActorMoveEvent(actor=actor, dt=dt), False)
@@ -159,11 +179,34 @@ class Actor(Impl):
 
                 actor.heading = heading.state
                 self.dispatch_event('on_try', actor.id, ActorMoveEvent(actor=actor, dt=dt), False)
+            elif actor.crouching != self.crouch_key: self.dispatch_event('on_try', actor.id, ActorMoveEvent(actor=actor, dt=0), False)
+            self.crouch_key = actor.crouching
+
+    # the sprite eases from where it was drawn toward px over disp_dt, the time simulated since it was last caught up,
+    # so both predicted and remote actors move smoothly regardless of how often updates arrive
+    def interpolate(self, dt):
+        if self.disp_dt > 0:
+            pos = self.px.into_screen((0, self.air_dz*TILE_RISE, 1+self.height+self.air_dz))
+            if math.dist(pos[:2], self.disp_pos[:2]) > TELEPORT_DISTANCE: self.disp_dt = 0
+            else:
+                self.disp_pos = self.disp_pos.lerp(pos, min(1, dt/self.disp_dt))
+                self.disp_dt = max(0, self.disp_dt-dt)
+        self.recalc()
+
+    # a correction from the server while caught up eases in from where the sprite was drawn rather than popping
+    def correct(self, was):
+        if self.disp_dt > 0: return
+        pos = self.px.into_screen((0, self.air_dz*TILE_RISE, 1+self.height+self.air_dz))
+        if CORRECTION_DISTANCE < math.dist(pos[:2], was[:2]) <= TELEPORT_DISTANCE:
+            self.disp_pos = was
+            self.disp_dt = CORRECTION_TIME
+            self.recalc()
 
     def recalc(self):
         super().recalc()
         if self.disp_dt <= 0: self.disp_pos = self.px.into_screen((0, self.air_dz*TILE_RISE, 1+self.height+self.air_dz))
         self.sprite.position = self.disp_pos[:3]
+        self.sprite.scale_y = self.scale_y*(CROUCH_HEIGHT/self.height if self.crouching else 1)
 
 Actor.register_event_type('on_overlay')
 
@@ -179,5 +222,5 @@ class ImplFactory:
         self.behaviour_factory = behaviour_factory
 
     def create(self, id, typ, px, **kwargs): 
-        behaviour = kwargs.pop("behaviour", typ)
+        behaviour = kwargs.pop("behaviour", ACTOR_TYPES.get(typ, {}).get("behaviour"))
         return Impl(id, typ, px, self.behaviour_factory.create(behaviour))
\ No newline at end of file
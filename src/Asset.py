@@ -82,8 +82,7 @@ class Factory:
         self.load("buildings.png", (1,1), (1,4/3), (1,96/136), (TILE_WIDTH/83,TILE_HEIGHT/96), FLAG_SOLID)
         self.load("decorators.png", (1,1), (1,1/3), (1,1/3), (TILE_WIDTH/27,TILE_HEIGHT*3/96), FLAG_NONE)
         self.load("ui.png", (3,1), (1,1), (0,0), (1,1), FLAG_NONE)
-        self.loadActor("blank.png", 4)
-        self.loadActor("dog.png", 4)
+        for i,it in ACTOR_TYPES.items(): self.loadActor(i+".png", it["frames"])
 
     def create_sprite(self, typ, idx, px=Px(0,0,0), **kwargs):
         batch = kwargs.pop("batch", self.batch)
@@ -1,12 +1,13 @@
-import random
-
 from Event import ActorMoveEvent
-from HxPx import Hx
+from HxPx import DIRECTIONS
 
 class Behaviour:
     def update(self, actor): pass
 
 class Dog(Behaviour):
+    def __init__(self, rng):
+        self.rng = rng
+
     def update(self, actor, dt):
         if actor.busy > 0:
             actor.dispatch_event('on_try', actor.id, ActorMoveEvent(actor.state, dt), False)
@@ -14,13 +15,14 @@ class Dog(Behaviour):
         if actor.busy > -2:
             actor.busy -= dt
         else:
-            actor.heading = random.choice([Hx(1,0,0), Hx(-1,0,0), 
-                                        Hx(0,1,0), Hx(0,-1,0),
-                                        Hx(1,-1,0), Hx(-1,1,0)])
-            magnitude = random.randint(1,3)
+            actor.heading = self.rng.choice(DIRECTIONS)
+            magnitude = self.rng.randint(1,3)
             actor.busy = magnitude*.5
             actor.dispatch_event('on_try', actor.id, ActorMoveEvent(actor.state, dt), False)
 
 class Factory:
+    def __init__(self, rng):
+        self.rng = rng
+
     def create(self, typ):
-        if typ == "dog": return Dog()
\ No newline at end of file
+        if typ == "dog": return Dog(self.rng)
\ No newline at end of file
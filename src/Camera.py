@@ -1,5 +1,9 @@
 import pyglet
 
+# modes for following the player, FREE is panned by dragging with the mouse
+FOLLOW = "follow"
+FREE = "free"
+
 class Camera:
     """ A simple 2D camera that contains the speed and offset."""
 
@@ -0,0 +1,106 @@
+import pyglet
+from pyglet.window import key
+
+from Config import *
+from Event import ChatEvent, ItemEquipEvent, ItemUnequipEvent, TradeEvent
+import Trade
+
+LOCAL = "local"
+WHISPER = "whisper"
+ZONE = "zone"
+
+PADDING = 8
+MARGIN = 80
+WIDTH = 400
+LINES = 8
+
+TRADE_LINES = {
+    Trade.REQUEST: "{sender} asks {target} to trade, /accept or /cancel",
+    Trade.ACCEPT: "{sender} accepted, /offer <count> <item> then /confirm",
+    Trade.OFFER: "{sender} offers {count} {typ}",
+    Trade.CONFIRM: "{sender} confirmed",
+    Trade.CANCEL: "trade cancelled",
+    Trade.DONE: "trade done",
+    Trade.FULL: "not enough room, make some then /confirm again",
+}
+
+class Chat(pyglet.event.EventDispatcher):
+    def __init__(self, batch):
+        self.lines = []
+        self.log = pyglet.text.Label("", multiline=True, width=WIDTH, x=PADDING, y=MARGIN+2*PADDING, anchor_y='bottom', batch=batch)
+
+        self.document = pyglet.text.document.UnformattedDocument("")
+        font = self.document.get_font()
+        height = font.ascent - font.descent
+        self.layout = pyglet.text.layout.IncrementalTextLayout(self.document, WIDTH, height, batch=batch)
+        self.layout.x = PADDING
+        self.layout.y = MARGIN
+        self.caret = pyglet.text.caret.Caret(self.layout, batch=batch)
+        self.border = pyglet.shapes.Rectangle(0, MARGIN-PADDING/2, WIDTH+2*PADDING, height+PADDING,
+                                              color=(255, 255, 255, 100), batch=batch)
+        self.visible = False
+
+    @property
+    def visible(self): return self.border.visible
+
+    @visible.setter
+    def visible(self, v):
+        self.document.text = ""
+        self.border.visible = v
+        self.caret.visible = v
+
+    def on_key_press(self, sym, mod):
+        if(sym == key.ENTER):
+            self.send(self.document.text.strip())
+            self.reset()
+        if(sym == key.ESCAPE):
+            self.reset()
+        return pyglet.event.EVENT_HANDLED
+
+    def on_text(self, text):
+        if text not in "\r\n": self.caret.on_text(text)
+        return pyglet.event.EVENT_HANDLED
+
+    def on_text_motion(self, motion, select=False):
+        self.caret.on_text_motion(motion, select)
+        return pyglet.event.EVENT_HANDLED
+
+    # "/equip <item>" and "/unequip <slot>" change what is worn, "/trade <id>" then "/accept", "/offer <count> <item>",
+    # "/confirm" and "/cancel" trade with another player, anything else is said
+    def send(self, text):
+        parts = text.split()
+        if not parts: return
+        if text.startswith("/equip "): self.dispatch_event("on_try", None, ItemEquipEvent(text[7:].strip()), True)
+        elif text.startswith("/unequip "): self.dispatch_event("on_try", None, ItemUnequipEvent(text[9:].strip()), True)
+        elif parts[0] == "/trade":
+            if len(parts) == 2 and parts[1].isdigit(): self.dispatch_event("on_try", None, TradeEvent(Trade.REQUEST, int(parts[1])), True)
+        elif parts[0] == "/offer":
+            if len(parts) == 3 and parts[1].isdigit(): self.dispatch_event("on_try", None, TradeEvent(Trade.OFFER, None, parts[2], int(parts[1])), True)
+        elif parts[0] in ("/accept", "/confirm", "/cancel"): self.dispatch_event("on_try", None, TradeEvent(parts[0][1:]), True)
+        else: self.say(text)
+
+    # "/w <id> text" whispers to a player, "/z text" speaks to the whole zone, anything else is local
+    def say(self, text):
+        channel, target = LOCAL, None
+        if text.startswith("/w "):
+            parts = text.split(" ", 2)
+            if len(parts) < 3 or not parts[1].isdigit(): return
+            channel, target, text = WHISPER, int(parts[1]), parts[2]
+        elif text.startswith("/z "): channel, text = ZONE, text[3:]
+        if text: self.dispatch_event("on_try", None, ChatEvent(channel, text, None, target), True)
+
+    def do_chat(self, tid, evt): self.write("[{}] {}: {}".format(evt.channel, evt.sender, evt.text))
+    def do_server_shutdown(self, tid, evt): self.write("server is shutting down")
+    def do_trade(self, tid, evt): self.write("[trade] " + TRADE_LINES.get(evt.action, "").format(sender=evt.sender, target=evt.target, count=evt.count, typ=evt.typ))
+    def do_change_inventory(self, tid, evt): self.write("carrying {}".format(", ".join("{} {}".format(it.count, it.typ) for it in evt.items)))
+
+    def write(self, line):
+        self.lines = (self.lines + [line])[-LINES:]
+        self.log.text = "\n".join(self.lines)
+
+    def reset(self):
+        self.visible = False
+        self.dispatch_event("on_close")
+
+Chat.register_event_type('on_close')
+Chat.register_event_type('on_try')
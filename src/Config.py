@@ -2,11 +2,73 @@ import logging
 from math import sqrt
 
 LOGLEVEL = logging.DEBUG
+DEV = False # enables tools for building levels, like undoing tile changes
 
 SERVER = "localhost"
 SERVER_PORT = 42424
 
+SETTINGS_FILE = "settings.json"
+
+SEED = 42
+MAP_PATH = "../data/maps"
+MAP_FILE = "default.0" # missing or empty until the server first saves
+WORLD_RADIUS = 1024
+
+# every kind of actor, sprites are read from assets/sprites/<typ>.png with frames columns of animation
+PLAYER_TYPE = "blank"
+ACTOR_TYPES = dict(
+    blank = dict(frames=4, behaviour=None),
+    dog = dict(frames=4, behaviour="dog"),
+)
+
+# chance a newly discovered tile spawns an npc, then (typ, weight, max within ACTIVE_RADIUS of the tile) to pick which
+SPAWN_CHANCE = 0.01
+SPAWN_TABLE = [("dog", 1, 5)]
+
+CHAT_LOCAL_RANGE = 20 # hexes
+CHAT_MAX_LENGTH = 200
+CHAT_RATE_LIMIT = 5 # messages per CHAT_RATE_PERIOD seconds
+CHAT_RATE_PERIOD = 10
+
+SERVER_TICK_RATE = 20
+SERVER_MAX_TICKS_PER_UPDATE = 5
+METRICS_PERIOD = 1 # seconds
+SHUTDOWN_GRACE = 2 # seconds to wait for clients to hear the server is stopping
+MAX_MOVE_DT = 1/SERVER_TICK_RATE
+
+# actor movement, read by both the client prediction and the server
+DEFAULT_SPEED = 120 # px per second
+DEFAULT_VERTICAL = 1.2 # jump height in tiles
+DEFAULT_HEIGHT = 3 # tiles
+ACTOR_RADIUS = 7 # px, collider size
+CROUCH_HEIGHT = 2 # tiles
+CROUCH_SPEED = 0.5 # of speed
+MANTLE_HEIGHT = 2 # tiles, the highest ledge an actor can climb by walking into it
+MANTLE_TIME = 0.3 # seconds walking into a ledge before climbing it
+
+# things a player can carry, drawn on the ground with a tile sprite shrunk by ITEM_SCALE
+# an item with a slot can be worn there, adding its mods to the wearer's speed and vertical
+ITEMS = dict(
+    wood = dict(stack=20, sprite=("decorators",0), slot="hand", mods=dict(vertical=0.5)),
+    stone = dict(stack=20, sprite=("buildings",0), slot="back", mods=dict(speed=-30)),
+)
+ITEM_DROPS = dict(decorators="wood", buildings="stone") # tiles of these sheets leave an item behind when cleared
+ITEM_SCALE = 0.4
+INVENTORY_SIZE = 8 # stacks
+PICKUP_REACH = 1 # hexes from the player a dropped item can be picked up
+TRADE_REACH = 3 # hexes between two players starting a trade
+
+EDIT_REACH = 1 # hexes from the player a tile can be changed
+EDIT_REACH_Z = 5 # levels above or below the player a tile can be changed
+EDIT_HISTORY = 100 # tile changes each player can undo with DEV
+
+# hexes are pointy topped and addressed by axial (q, r) with z counting whole tiles of elevation, see HxPx
+# on screen a hex is TILE_SIZE px from centre to corner, squashed vertically by ISO_SCALE, and each z lifts it by RISE of its height
 ISO_SCALE = 3/4
+RISE = 5/6
+
+VIEW_RADIUS = 5 # hexes discovered and drawn around a player
+ACTIVE_RADIUS = VIEW_RADIUS*3 # npcs this close to a player are simulated
 
 DEPTH = 100
 TILE_SIZE = 24
@@ -17,7 +79,7 @@ SQRT3 = sqrt(3)
 
 TILE_SIZE_H = TILE_SIZE
 TILE_SIZE_W = round(TILE_SIZE * SQRT3) / SQRT3
-TILE_RISE = ISO_SCALE*TILE_SIZE_H*(5/6)
+TILE_RISE = ISO_SCALE*TILE_SIZE_H*RISE
 TILE_WIDTH = SQRT3*TILE_SIZE_W
 TILE_HEIGHT = ISO_SCALE*TILE_SIZE_H*2
 
@@ -16,6 +16,13 @@ class ActorUnloadEvent(Event):
     actor: quickle.Struct
     event = "unload_actor"
 
+class ChatEvent(Event):
+    channel: str
+    text: str
+    sender: int = None
+    target: int = None
+    event = "chat"
+
 class ConnectionInitEvent(Event):
     tid: int
     event = "init_connection"
@@ -23,6 +30,44 @@ class ConnectionInitEvent(Event):
 class SceneLoadEvent(Event):
     event = "load_scene"
 
+class EquipmentChangeEvent(Event):
+    id: int
+    slots: dict
+    event = "change_equipment"
+
+class InventoryChangeEvent(Event):
+    id: int
+    items: list
+    event = "change_inventory"
+
+class ItemDropEvent(Event):
+    hx: tuple
+    item: quickle.Struct
+    event = "drop_item"
+
+class ItemEquipEvent(Event):
+    typ: str
+    event = "equip_item"
+
+class ItemPickupEvent(Event):
+    hx: tuple
+    event = "pickup_item"
+
+class ItemUnequipEvent(Event):
+    slot: str
+    event = "unequip_item"
+
+class ServerShutdownEvent(Event):
+    event = "server_shutdown"
+
+class TradeEvent(Event):
+    action: str
+    target: int = None
+    typ: str = None
+    count: int = None
+    sender: int = None
+    event = "trade"
+
 class TileChangeEvent(Event):
     hx: quickle.Struct
     tile: quickle.Struct
@@ -32,10 +77,24 @@ class TileDiscoverEvent(Event):
     hx: tuple
     event = "discover_tile"
 
+class TileUndoEvent(Event):
+    redo: bool = False
+    event = "undo_tile"
+
 REGISTRY = [ActorMoveEvent, 
             ActorLoadEvent, 
             ActorUnloadEvent, 
+            ChatEvent,
             ConnectionInitEvent, 
+            EquipmentChangeEvent,
+            InventoryChangeEvent,
+            ItemDropEvent,
+            ItemEquipEvent,
+            ItemPickupEvent,
+            ItemUnequipEvent,
             SceneLoadEvent,
+            ServerShutdownEvent,
             TileChangeEvent,
-            TileDiscoverEvent]
+            TileDiscoverEvent,
+            TileUndoEvent,
+            TradeEvent]
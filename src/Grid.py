@@ -0,0 +1,41 @@
+import pyglet
+
+from Config import *
+
+COLOR = (255, 255, 255, 150)
+FONT_SIZE = 6
+
+class Grid:
+    def __init__(self, scene, batch):
+        self.scene = scene
+        self.batch = batch
+        self.visible = False
+        self.hx = None
+        self.shapes = []
+
+    def toggle(self):
+        self.visible = not self.visible
+        self.hx = None
+        if not self.visible: self.clear()
+
+    def clear(self):
+        for it in self.shapes: it.delete()
+        self.shapes = []
+
+    # rebuilt only when the player changes tile or a tile changes near them
+    def update(self, hx):
+        if not self.visible or hx == self.hx: return
+        self.hx = hx
+        self.clear()
+        for i,it in list(self.scene.tiles.items()):
+            if i.dist(hx) > VIEW_RADIUS: continue
+            vertices = [v.into_screen() for v in it.px.vertices()]
+            for j in range(len(vertices)):
+                a, b = vertices[j], vertices[(j+1) % len(vertices)]
+                self.shapes.append(pyglet.shapes.Line(a.x, a.y, b.x, b.y, color=COLOR, batch=self.batch))
+            pos = it.px.into_screen()
+            self.shapes.append(pyglet.text.Label("{},{},{}".format(*i.state), font_size=FONT_SIZE, x=pos.x, y=pos.y,
+                                                 anchor_x='center', anchor_y='center', color=COLOR, batch=self.batch))
+
+    def do_change_tile(self, tid, evt):
+        self.hx = None
@@ -69,6 +69,12 @@ class Hx:
     @property
     def s(self): return -self.q-self.r
 
+    # round a delta of any length to the closest of the 6 DIRECTIONS, ties resolve consistently
+    def nearest_direction(self):
+        d = max(abs(self.q), abs(self.r), abs(self.s))
+        if d == 0: return Hx(0,0,0)
+        return hex_round(self.q/d, self.r/d, 0)
+
     def into_px(self, tile_size = TILE_SIZE, orientation = ORIENTATION_PNTY):
         tile_size_w = TILE_SIZE_W if TILE_SIZE==tile_size else round(tile_size * sqrt(3)) / sqrt(3)
         x = (orientation[0][0] * self.q + orientation[0][1] * self.r) * (tile_size_w)
@@ -80,7 +86,37 @@ class Hx:
           + abs(self.q + self.r - other.q - other.r)
           + abs(self.r - other.r)) / 2
 
+    # dist is flat, this also keeps other within height tiles up or down
+    def within(self, other, radius, height): return self.dist(other) <= radius and abs(self.z - other.z) <= height
+
+    # every hex from self to other inclusive, z is interpolated between the two
+    # lines are always walked from the same end so a->b is the reverse of b->a
+    def line_to(self, other):
+        if (other.q, other.r, other.z) < (self.q, self.r, self.z): return list(reversed(other.line_to(self)))
+        n = int(self.dist(other))
+        if n == 0: return [Hx(*self.state)] if self == other else [Hx(*self.state), Hx(*other.state)]
+        return [hex_round(self.q + (other.q-self.q)*i/n + 1e-6, 
+                          self.r + (other.r-self.r)*i/n + 2e-6, 
+                          round(self.z + (other.z-self.z)*i/n)) for i in range(n+1)]
+
+    def neighbors(self): return [self+it for it in DIRECTIONS]
+
+    # hexes exactly radius away at the same z, walked counter clockwise from the south west corner
+    def ring(self, radius):
+        if radius == 0: return [Hx(*self.state)]
+        ring = []
+        hx = self + Hx(DIRECTIONS[4].q*radius, DIRECTIONS[4].r*radius, 0)
+        for it in DIRECTIONS:
+            for _ in range(radius):
+                ring.append(hx)
+                hx = hx+it
+        return ring
+
+    # every hex within radius, nearest rings first
+    def spiral(self, radius): return [it for i in range(radius+1) for it in self.ring(i)]
+
     @property
     def state(self): return (self.q, self.r, self.z)
-    
+
+DIRECTIONS = [Hx(+1,0,0),Hx(+1,-1,0),Hx(0,-1,0),Hx(-1,0,0),Hx(-1,+1,0),Hx(0,+1,0)]
 
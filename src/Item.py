@@ -0,0 +1,65 @@
+import quickle
+
+from Config import *
+
+class Stack(quickle.Struct):
+    typ: str
+    count: int
+
+# what a player carries, at most INVENTORY_SIZE stacks each holding up to the stack size in ITEMS
+class Inventory:
+    def __init__(self): self.items = []
+
+    # fills existing stacks before starting new ones, returns how many didn't fit
+    def add(self, typ, count):
+        size = ITEMS[typ]["stack"]
+        for it in self.items:
+            if it.typ != typ: continue
+            n = min(count, size-it.count)
+            it.count += n
+            count -= n
+        while count > 0 and len(self.items) < INVENTORY_SIZE:
+            n = min(count, size)
+            self.items.append(Stack(typ, n))
+            count -= n
+        return count
+
+    def count(self, typ): return sum(it.count for it in self.items if it.typ == typ)
+
+    # takes from the last stacks first, nothing is taken unless there is count of typ
+    def remove(self, typ, count):
+        if count > self.count(typ): return False
+        for it in reversed(self.items):
+            if it.typ != typ: continue
+            n = min(count, it.count)
+            it.count -= n
+            count -= n
+        self.items = [it for it in self.items if it.count > 0]
+        return True
+
+    @property
+    def state(self): return [Stack(it.typ, it.count) for it in self.items]
+
+    @state.setter
+    def state(self, v): self.items = [Stack(it.typ, it.count) for it in v]
+
+# one item worn in each slot, each adds its mods on top of the actor's own attributes
+class Equipment:
+    def __init__(self): self.slots = {}
+
+    # returns whatever was in the slot before
+    def equip(self, typ):
+        slot = ITEMS[typ]["slot"]
+        was = self.slots.get(slot)
+        self.slots[slot] = typ
+        return was
+
+    def unequip(self, slot): return self.slots.pop(slot, None)
+
+    def mod(self, attr): return sum(ITEMS[it].get("mods", {}).get(attr, 0) for it in self.slots.values())
+
+    @property
+    def state(self): return dict(self.slots)
+
+    @state.setter
+    def state(self, v): self.slots = dict(v)
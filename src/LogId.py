@@ -3,5 +3,10 @@ from enum import Enum
 LOGID = Enum('LOGID',[
     'UNDEFINED',
     'NF_ACTOR',
-    'SKIP_SEQ'
+    'SKIP_SEQ',
+    'REPLAY_MISMATCH',
+    'BAD_INPUT',
+    'BAD_SETTINGS',
+    'NON_FINITE',
+    'BAD_MAP'
 ])
\ No newline at end of file
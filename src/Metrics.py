@@ -0,0 +1,40 @@
+import json
+import time
+
+# one json object per line, counters cover the period since the previous sample
+class Metrics:
+    def __init__(self, path):
+        self.file = open(path, "a")
+        self.reset()
+
+    def reset(self):
+        self.ticks = 0
+        self.tick_time = 0
+        self.tick_max = 0
+        self.received = 0
+        self.sent = 0
+
+    def tick(self, duration):
+        self.ticks += 1
+        self.tick_time += duration
+        self.tick_max = max(self.tick_max, duration)
+
+    def sample(self, dt, sessions, scene):
+        npcs = {}
+        for it in scene.npcs.values(): npcs[it.typ] = npcs.get(it.typ, 0) + 1
+        self.file.write(json.dumps(dict(
+            time = time.time(),
+            period = dt,
+            clients = len(sessions),
+            pcs = len(scene.pcs),
+            npcs = npcs,
+            tiles = len(scene.tiles),
+            ticks = self.ticks,
+            tick_avg = self.tick_time/self.ticks if self.ticks else 0,
+            tick_max = self.tick_max,
+            received = self.received,
+            sent = self.sent)) + "\n")
+        self.file.flush()
+        self.reset()
+
+    def close(self): self.file.close()
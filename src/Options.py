@@ -0,0 +1,65 @@
+import pyglet
+from pyglet.window import key
+
+from Settings import CHOICES
+
+PADDING = 8
+WIDTH = 240
+
+# UP and DOWN pick a setting and LEFT and RIGHT change it, changes apply straight away and are saved with the rest of the settings
+class Options(pyglet.event.EventDispatcher):
+    def __init__(self, settings, window, camera, batch):
+        self.settings = settings
+        self.window = window
+        self.camera = camera
+        self.rows = ["camera", "fullscreen", "zoom"]
+        self.curr = 0
+        self.border = pyglet.shapes.Rectangle(0, 0, WIDTH+2*PADDING, 0, color=(255, 255, 255, 100), batch=batch)
+        self.label = pyglet.text.Label("", multiline=True, width=WIDTH, anchor_x='center', anchor_y='center', batch=batch)
+        self.visible = False
+
+    @property
+    def visible(self): return self.border.visible
+
+    @visible.setter
+    def visible(self, v):
+        self.border.visible = v
+        self.label.visible = v
+        if v: self.update()
+
+    def on_key_press(self, sym, mod):
+        if sym in (key.ESCAPE, key.ENTER, key.O): 
+            self.reset()
+            return pyglet.event.EVENT_HANDLED
+        if sym == key.UP: self.curr = (self.curr-1) % len(self.rows)
+        if sym == key.DOWN: self.curr = (self.curr+1) % len(self.rows)
+        if sym == key.LEFT: self.change(self.rows[self.curr], -1)
+        if sym == key.RIGHT: self.change(self.rows[self.curr], +1)
+        self.update()
+        return pyglet.event.EVENT_HANDLED
+
+    def change(self, row, step):
+        if row in CHOICES:
+            choices = CHOICES[row]
+            self.settings[row] = choices[(choices.index(self.settings[row])+step) % len(choices)]
+        elif row == "fullscreen":
+            self.settings["fullscreen"] = not self.settings["fullscreen"]
+            self.window.set_fullscreen(self.settings["fullscreen"])
+        elif row == "zoom":
+            self.camera.zoom += step*.1
+            self.settings["zoom"] = self.camera.zoom
+
+    def update(self):
+        self.label.text = "\n".join("{} {}: {}".format(">" if i == self.curr else " ", it, 
+                                                       "{:.1f}".format(self.settings[it]) if type(self.settings[it]) is float else self.settings[it]) 
+                                    for i,it in enumerate(self.rows))
+        self.label.position = (self.window.width/2, self.window.height/2, 0)
+        self.border.x = self.window.width/2 - self.border.width/2
+        self.border.y = self.window.height/2 - self.label.content_height/2 - PADDING
+        self.border.height = self.label.content_height + 2*PADDING
+
+    def reset(self):
+        self.visible = False
+        self.dispatch_event("on_close")
+
+Options.register_event_type('on_close')
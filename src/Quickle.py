@@ -2,7 +2,8 @@ import quickle
 
 import Tile
 import Actor
+import Item
 from Event import REGISTRY
 
-ENCODER = quickle.Encoder(registry=REGISTRY+[Tile.State, Actor.State])
-DECODER = quickle.Decoder(registry=REGISTRY+[Tile.State, Actor.State])
+ENCODER = quickle.Encoder(registry=REGISTRY+[Tile.State, Actor.State, Item.Stack])
+DECODER = quickle.Decoder(registry=REGISTRY+[Tile.State, Actor.State, Item.Stack])
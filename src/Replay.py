@@ -0,0 +1,80 @@
+from collections import deque
+from logging import info, warning
+
+from Event import ActorLoadEvent
+from LogId import LOGID
+from Quickle import ENCODER, DECODER
+
+# A replay is a sequence of length prefixed records:
+#   header - dict of rng state, tiles and npcs when recording began
+#   tick   - (dt, [encoded (tid, evt, seq), ...]) for every server update
+#   final  - dict of actor id to px when recording ended
+
+class Recorder:
+    def __init__(self, path, rng, scene):
+        self.file = open(path, "wb")
+        self.events = []
+        self.write(dict(rng=rng.getstate(),
+                        tiles=scene.state,
+                        actors=[it.state for it in scene.npcs.values()]))
+
+    def write(self, it):
+        it = ENCODER.dumps(it)
+        self.file.write(len(it).to_bytes(4, 'big', signed=False))
+        self.file.write(it)
+
+    # events are encoded as they arrive, before handlers get a chance to modify them
+    def record(self, tid, evt, seq): self.events.append(ENCODER.dumps((tid, evt, seq)))
+
+    def tick(self, dt):
+        self.write((dt, self.events))
+        self.events = []
+
+    def close(self, scene):
+        info("closing replay")
+        self.write(dict([(i, it.px.state) for i,it in list(scene.pcs.items()) + list(scene.npcs.items())]))
+        self.file.close()
+
+class Player:
+    def __init__(self, path):
+        records = []
+        data = open(path, "rb").read()
+        i = 0
+        while i+4 <= len(data):
+            sz = int.from_bytes(data[i:i+4], 'big', signed=False)
+            i += 4
+            if len(data[i:]) < sz:
+                warning("replay truncated after {} records".format(len(records)))
+                break
+            records.append(DECODER.loads(data[i:i+sz]))
+            i += sz
+        self.header = records[0]
+        self.ticks = deque([it for it in records[1:] if isinstance(it, tuple)])
+        self.expected = next((it for it in records[1:] if isinstance(it, dict)), None)
+        info("{} ticks loaded from replay".format(len(self.ticks)))
+
+    def begin(self, rng, scene):
+        rng.setstate(self.header["rng"])
+        scene.tiles = scene.from_state(self.header["tiles"])
+        for it in self.header["actors"]:
+            scene.do_load_actor(None, ActorLoadEvent(it))
+            scene.npcs[it.id].state = it
+
+    def next(self, incoming):
+        if not self.ticks: return None
+        dt, events = self.ticks.popleft()
+        incoming.extend([DECODER.loads(it) for it in events])
+        return dt
+
+    def verify(self, scene):
+        if self.expected is None:
+            warning("replay has no final state to verify against")
+            return False
+        actual = dict([(i, it.px.state) for i,it in list(scene.pcs.items()) + list(scene.npcs.items())])
+        ok = True
+        for i in set(actual.keys()) | set(self.expected.keys()):
+            if actual.get(i) != self.expected.get(i):
+                warning("{:} - actor {}: {} != {}".format(LOGID.REPLAY_MISMATCH, i, actual.get(i), self.expected.get(i)))
+                ok = False
+        if ok: info("replay matches recorded state for {} actors".format(len(actual)))
+        return ok
@@ -1,21 +1,26 @@
+from collections import deque
 import random
 import collision
-from logging import info, warning
+from logging import debug, info, warning
 import math
+import os
 import pyglet
 
+import Actor
 from Config import *
 from Event import *
 from HxPx import Hx, Px
+import Item
 from LogId import LOGID
-from Quickle import DECODER
+from Quickle import ENCODER, DECODER
 from StateManager import ACTION_BAR
+import Tile
+import Trade
 
-R=5
-NEIGHBORS = [Hx(+1,0,0),Hx(+1,-1,0),Hx(0,-1,0),Hx(-1,0,0),Hx(-1,+1,0),Hx(0,+1,0)]
+UNEXPLORED_COLOR = (100,100,100)
 
 class Impl(pyglet.event.EventDispatcher):
-    def __init__(self, actor_factory, asset_factory, state_manager, generator):
+    def __init__(self, actor_factory, asset_factory, state_manager, generator, rng):
         self.actor_factory = actor_factory
         self.asset_factory = asset_factory
         self.state_manager = state_manager
@@ -23,13 +28,22 @@ class Impl(pyglet.event.EventDispatcher):
         self.pcs = {}
         self.npcs = {}
         self.decorations = {}
+        self.edits = {}
+        self.drops = {}
+        self.trades = {}
         self.generator = generator
+        self.rng = rng
 
-    def try_load_actor(self, tid, evt): self.dispatch_event("on_do", None, evt)
+    # actors are only loaded and unloaded by the server itself, never on a client's say so
+    def try_load_actor(self, tid, evt):
+        if tid is not None:
+            warning("{:} - rejected load from {}: {}".format(LOGID.BAD_INPUT, tid, evt))
+            return
+        self.dispatch_event("on_do", None, evt, True)
     def do_load_actor(self, tid, evt):
-        actors = self.pcs if evt.actor.typ == "blank" else self.npcs
+        actors = self.pcs if evt.actor.typ == PLAYER_TYPE else self.npcs
         while evt.actor.id is None:
-            id = random.randint(0, pow(2,32)-1)
+            id = self.rng.randint(0, pow(2,32)-1)
             if actors.get(id, None) is None: evt.actor.id = id
         actor = self.actor_factory.create(evt.actor.id, evt.actor.typ, evt.actor.px)
         self.state_manager.push_handlers(actor)
@@ -38,11 +52,34 @@ class Impl(pyglet.event.EventDispatcher):
         actor.push_handlers(self)
         actors[evt.actor.id] = actor
 
+    # a tid of None is the server stepping an airborne actor, anything else only gets to choose heading, crouch and jump
     def try_move_actor(self, tid, evt):
-        actors = self.pcs if evt.actor.typ == "blank" else self.npcs
-        actor = actors[evt.actor.id]
-        if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.actor.id))
+        if not isinstance(evt.actor, Actor.State) or type(evt.actor.id) is not int or not isinstance(evt.dt, (int, float)) \
+           or not(isinstance(evt.actor.heading, tuple) and len(evt.actor.heading) == 3 and all(type(i) is int for i in evt.actor.heading)) \
+           or (tid is not None and tid != evt.actor.id):
+            warning("{:} - rejected move from {}: {}".format(LOGID.BAD_INPUT, tid, evt))
+            return
+        # a heading has to be one of the DIRECTIONS or none at all
+        if Hx(*evt.actor.heading).nearest_direction() != Hx(*evt.actor.heading):
+            warning("{:} - rejected move from {}: {}".format(LOGID.BAD_INPUT, tid, evt.actor))
+            return
+        actors = self.pcs if evt.actor.typ == PLAYER_TYPE else self.npcs
+        actor = actors.get(evt.actor.id)
+        if actor is None: 
+            warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.actor.id))
+            return
         state = actor.state
+        if (tid is None) != (state.air_time is not None):
+            debug("ignored move for {} while {}".format(evt.actor.id, "grounded" if tid is None else "airborne"))
+            return
+        evt.dt = max(0, min(evt.dt, MAX_MOVE_DT))
+
+        jump = state.air_time is None and evt.actor.air_time == 0
+        crouch = evt.actor.crouching is True
+        heading = evt.actor.heading
+        evt.actor = actor.state
+        evt.actor.heading = heading
+        if state.air_time is not None: actor.airborne(evt.actor, evt.dt)
 
         px = Px(*state.px)
         hx = px.into_hx()
@@ -50,10 +87,28 @@ class Impl(pyglet.event.EventDispatcher):
         heading_px = heading_hx.into_px()
         heading_offset_px = heading_px-px
         heading_offset_angle = math.atan2(heading_offset_px.y, heading_offset_px.x)
-        new_px = px + Px(state.speed*evt.dt*math.cos(heading_offset_angle), ISO_SCALE*state.speed*evt.dt*math.sin(heading_offset_angle), 0)
+        # with no heading, or already at the centre of the hex headed for, there is no direction to move in
+        moving = heading_hx != hx and (heading_offset_px.x != 0 or heading_offset_px.y != 0)
+
+        # crouching is only possible on the ground, and can't be stood up from under a low ceiling
+        crouching = crouch and state.air_time is None and not jump
+        if state.crouching and state.air_time is None:
+            for z in range(CROUCH_HEIGHT+1, state.height+1):
+                it = self.tiles.get(hx+Hx(0,0,z))
+                if it is not None and it.flags & FLAG_SOLID:
+                    crouching = True
+                    jump = False
+                    break
+        evt.actor.crouching = crouching
+        height = CROUCH_HEIGHT if crouching else state.height
+        speed = state.speed*(CROUCH_SPEED if crouching else 1)
 
-        if state.air_time is None and not(evt.actor.air_time == 0):
-            evt.actor.air_time = None
+        if moving: new_px = px + Px(speed*evt.dt*math.cos(heading_offset_angle), ISO_SCALE*speed*evt.dt*math.sin(heading_offset_angle), 0)
+        else: new_px = Px(px.x, px.y, px.z)
+        if not self.in_bounds(new_px.into_hx()): new_px = Px(px.x, px.y, px.z)
+
+        if jump:
+            evt.actor.air_time = 0
             evt.actor.air_dz = 0
 
         if state.air_time is not None and state.air_dz > 1:
@@ -74,63 +129,278 @@ class Impl(pyglet.event.EventDispatcher):
             else:
                 it = self.tiles.get(new_px.into_hx())
                 if it is None or not(it.flags & FLAG_SOLID):
-                    evt.actor.air_time = state.vertical*(TILE_RISE*2)/state.speed
+                    evt.actor.air_time = state.vertical*(TILE_RISE*2)/state.speed if state.speed > 0 else 0
                     evt.actor.air_dz = 0
-        
-        if evt.actor.air_dz is None: evt.actor.air_dz = state.air_dz
+
 
         collider = collision.Poly(collision.Vector(new_px.x, new_px.y), 
-                                    [collision.Vector(it.x, it.y) for it in Px(0,0,0).vertices(7, ORIENTATION_FLAT)], 0)
+                                    [collision.Vector(it.x, it.y) for it in Px(0,0,0).vertices(ACTOR_RADIUS, ORIENTATION_FLAT)], 0)
         response = collision.Response()
-        for neighbor in [it+Hx(0,0,z+1+max(0,math.floor(evt.actor.air_dz))) for it in NEIGHBORS for z in range(state.height)]:
-            it = self.tiles.get(hx+neighbor)
+        blocked = False
+        for neighbor in [it+Hx(0,0,z+1+max(0,math.floor(evt.actor.air_dz))) for it in hx.neighbors() for z in range(height)]:
+            it = self.tiles.get(neighbor)
             response.reset()
             if it is not None and it.sprite is not None and collision.collide(collider, it.collider, response): 
-                if heading_hx == it.hx - Hx(0, 0, it.hx.z-heading_hx.z): 
+                if not moving or heading_hx == it.hx - Hx(0, 0, it.hx.z-heading_hx.z): 
                     new_px = Px(px.x, px.y, new_px.z)
+                    blocked = True
                     break
                 heading_offset_px = heading_px - Px(*it.collider.pos, 0)
                 heading_offset_angle = math.atan2(heading_offset_px.y, heading_offset_px.x)
-                new_px = px + Px(state.speed*evt.dt*math.cos(heading_offset_angle), ISO_SCALE*state.speed*evt.dt*math.sin(heading_offset_angle), 0)
+                new_px = px + Px(speed*evt.dt*math.cos(heading_offset_angle), ISO_SCALE*speed*evt.dt*math.sin(heading_offset_angle), 0)
                 break
 
+        # walking into a ledge too high to step onto climbs it after MANTLE_TIME, only ever from the ground
+        evt.actor.mantle = 0
+        top = self.ledge(hx, heading_hx, height) if moving and blocked and state.air_time is None and not jump else None
+        if top is not None:
+            evt.actor.mantle = state.mantle + evt.dt
+            if evt.actor.mantle >= MANTLE_TIME:
+                new_px = Px(heading_px.x, heading_px.y, top)
+                evt.actor.mantle = 0
+        if not all(math.isfinite(it) for it in new_px.state+(evt.actor.air_dz,)):
+            warning("{:} - {} moved to {} {}, staying at {}".format(LOGID.NON_FINITE, evt.actor.id, new_px.state, evt.actor.air_dz, px.state))
+            new_px = px
+            evt.actor.air_dz = state.air_dz
+        if not self.in_bounds(new_px.into_hx()): new_px = Px(px.x, px.y, new_px.z)
+
         evt.actor.px = new_px.state
-        self.dispatch_event("on_do", tid, evt, True)
+        self.dispatch_event("on_do", evt.actor.id, evt, True)
+
+    # z of the top of the ledge in heading_hx if it is at most MANTLE_HEIGHT up with room to stand on it
+    def ledge(self, hx, heading_hx, height):
+        solid = lambda it: it is not None and it.flags & FLAG_SOLID
+        for z in range(MANTLE_HEIGHT, 0, -1):
+            if solid(self.tiles.get(heading_hx+Hx(0,0,z))): break
+        else: return None
+        if any(solid(self.tiles.get(heading_hx+Hx(0,0,z+i))) for i in range(1, height+1)): return None
+        if any(solid(self.tiles.get(hx+Hx(0,0,i))) for i in range(height+1, z+height+1)): return None
+        return hx.z+z
+
     def do_move_actor(self, tid, evt): 
-        actors = self.pcs if evt.actor.typ == "blank" else self.npcs
+        actors = self.pcs if evt.actor.typ == PLAYER_TYPE else self.npcs
         actor = actors.get(evt.actor.id,None)
         if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.actor.id))
         else: actor.state = evt.actor
 
-    def try_unload_actor(self, tid, evt): self.dispatch_event("on_do", tid, evt, True)
+    def try_unload_actor(self, tid, evt):
+        if tid is not None:
+            warning("{:} - rejected unload from {}: {}".format(LOGID.BAD_INPUT, tid, evt))
+            return
+        self.dispatch_event("on_do", None, evt, True)
     def do_unload_actor(self, tid, evt):
-        actors = self.pcs if evt.actor.typ == "blank" else self.npcs
+        actors = self.pcs if evt.actor.typ == PLAYER_TYPE else self.npcs
         actor = actors.get(evt.actor.id,None)
         if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.actor.id))
-        else: del actors[evt.actor.id]
+        else:
+            if evt.actor.id in self.trades: self.cancel_trade(self.trades[evt.actor.id])
+            del actors[evt.actor.id]
+            self.remove_handlers(actor)
+            self.state_manager.remove_handlers(actor)
+        self.edits.pop(evt.actor.id, None)
 
     def try_discover_tile(self, tid, evt):
-        c = Hx(*evt.hx)
-        for q in range(-R, R+1):
-            r1 = max(-R, -q-R)
-            r2 = min( R, -q+R)
-            for r in range(r1,r2+1):
-                hx = c + Hx(q,r,0)
-                
-                hx.z = self.generator.elevation(Hx(hx.q,hx.r,0))
-                if self.tiles.get(hx) is not None: continue
-                tile = self.asset_factory.create_tile("biomes", 1 if hx.z < 50 else 3 if hx.z < 75 else 5, hx.into_px())
-                self.dispatch_event("on_do", None, TileChangeEvent(hx.state, tile.state), True)
+        for hx in Hx(*evt.hx).spiral(VIEW_RADIUS):
+            if not self.in_bounds(hx): continue
+
+            hx.z = self.generator.elevation(Hx(hx.q,hx.r,0))
+            if self.tiles.get(hx) is not None: continue
+            tile = self.asset_factory.create_tile("biomes", 1 if hx.z < 50 else 3 if hx.z < 75 else 5, hx.into_px())
+            self.dispatch_event("on_do", None, TileChangeEvent(hx.state, tile.state), True)
 
-                if random.randint(0,100) == 100:
-                    self.dispatch_event("on_do", None, ActorLoadEvent(self.actor_factory.create(None, "dog", hx.into_px()).state), True)
+            typ = self.roll_spawn(hx)
+            if typ is not None:
+                self.dispatch_event("on_do", None, ActorLoadEvent(self.actor_factory.create(None, typ, hx.into_px()).state), True)
 
-                hx.z += 1
-                if self.generator.vegetation(Hx(hx.q,hx.r,0)) > 66:
-                    tile = self.asset_factory.create_tile("decorators", 0, hx.into_px())
-                    self.dispatch_event("on_do", None, TileChangeEvent(hx.state, tile.state), True)
+            hx.z += 1
+            if self.generator.vegetation(Hx(hx.q,hx.r,0)) > 66:
+                tile = self.asset_factory.create_tile("decorators", 0, hx.into_px())
+                self.dispatch_event("on_do", None, TileChangeEvent(hx.state, tile.state), True)
                 
-    def try_change_tile(self, tid, evt): self.dispatch_event("on_do", tid, evt, True)
+    def roll_spawn(self, hx):
+        if self.rng.random() >= SPAWN_CHANCE: return None
+        counts = {}
+        for it in self.npcs.values(): 
+            if it.hx.dist(hx) <= ACTIVE_RADIUS: counts[it.typ] = counts.get(it.typ, 0) + 1
+        table = [(typ, weight) for typ, weight, limit in SPAWN_TABLE if counts.get(typ, 0) < limit]
+        if not table: return None
+        return self.rng.choices([it[0] for it in table], weights=[it[1] for it in table])[0]
+
+    # top solid tile of every column within radius that a line of sight from eye reaches,
+    # a column that rises above the line hides everything behind it
+    def visible_hexes(self, eye, radius):
+        tops = {}
+        for i,it in list(self.tiles.items()):
+            if it.flags & FLAG_SOLID and i.dist(eye) <= radius: tops[(i.q,i.r)] = max(i.z, tops.get((i.q,i.r), i.z))
+        visible = set()
+        for (q,r),z in tops.items():
+            line = eye.line_to(Hx(q,r,z))
+            if all(tops.get((it.q,it.r), it.z) <= it.z for it in line[1:-1]): visible.add(Hx(q,r,z))
+        return visible
+
+    # the edge of the world blocks like a wall rather than letting actors fall into the void
+    def in_bounds(self, hx): return hx.dist(Hx(0,0,0)) <= WORLD_RADIUS
+
+    def try_change_tile(self, tid, evt):
+        actor = self.pcs.get(tid)
+        if actor is None or not self.valid_change(actor, evt):
+            warning("{:} - rejected tile change from {}: {} {}".format(LOGID.BAD_INPUT, tid, evt.hx, evt.tile))
+            return
+        before = self.tiles.get(Hx(*evt.hx))
+        if DEV:
+            undo, redo = self.edits.setdefault(tid, (deque(maxlen=EDIT_HISTORY), deque(maxlen=EDIT_HISTORY)))
+            undo.append((evt.hx, before.state if before is not None else None, evt.tile))
+            redo.clear()
+        self.change_tile(tid, evt)
+        if evt.tile is None and before is not None and before.sprite._typ in ITEM_DROPS: self.drop_item(Hx(*evt.hx), ITEM_DROPS[before.sprite._typ], 1)
+
+    # dev only, each player steps back and forth through their own edits
+    def try_undo_tile(self, tid, evt):
+        if not DEV or tid not in self.pcs:
+            warning("{:} - rejected undo from {}".format(LOGID.BAD_INPUT, tid))
+            return
+        undo, redo = self.edits.get(tid, ([], []))
+        src, dst = (redo, undo) if evt.redo is True else (undo, redo)
+        if not src: return
+        hx, before, after = src[-1]
+        tile = after if evt.redo is True else before
+        if self.occupied(Hx(*hx), tile): return
+        dst.append(src.pop())
+        self.change_tile(tid, TileChangeEvent(hx, tile))
+
+    def change_tile(self, tid, evt):
+        self.dispatch_event("on_do", tid, evt, True)
+
+        # anyone left standing on a removed tile falls
+        hx = Hx(*evt.hx)
+        if evt.tile is None or not(self.asset_factory._assets[evt.tile.sprite__typ][evt.tile.sprite__idx].flags & FLAG_SOLID):
+            for i,it in list(self.pcs.items()) + list(self.npcs.items()):
+                if it.hx == hx and it.air_time is None: self.try_move_actor(i, ActorMoveEvent(it.state, 0))
+
+    def valid_change(self, actor, evt):
+        if not(isinstance(evt.hx, tuple) and len(evt.hx) == 3 and all(isinstance(i, (int, float)) and float(i).is_integer() for i in evt.hx)): return False
+        hx = Hx(*evt.hx)
+        if not self.in_bounds(hx) or not hx.within(actor.hx, EDIT_REACH, EDIT_REACH_Z): return False
+        if evt.tile is None: return True
+        if not isinstance(evt.tile, Tile.State): return False
+        assets = self.asset_factory._assets.get(evt.tile.sprite__typ) if isinstance(evt.tile.sprite__typ, str) else None
+        if assets is None or type(evt.tile.sprite__idx) is not int or not(0 <= evt.tile.sprite__idx < len(assets)): return False
+        return not self.occupied(hx, evt.tile)
+
+    # one stack lies in each hex, anything that doesn't match what is already there is lost
+    def drop_item(self, hx, typ, count):
+        it = self.drops.get(hx)
+        if it is not None and it.typ != typ: return
+        self.dispatch_event("on_do", None, ItemDropEvent(hx.state, Item.Stack(typ, count + (it.count if it is not None else 0))), True)
+
+    # shared by the client choosing what to ask for and the server deciding if it may have it
+    def in_reach(self, actor, hx): return hx.within(actor.hx, PICKUP_REACH, actor.height)
+
+    def try_pickup_item(self, tid, evt):
+        actor = self.pcs.get(tid)
+        if actor is None or not(isinstance(evt.hx, tuple) and len(evt.hx) == 3 and all(type(i) is int for i in evt.hx)):
+            warning("{:} - rejected pickup from {}: {}".format(LOGID.BAD_INPUT, tid, evt.hx))
+            return
+        hx = Hx(*evt.hx)
+        it = self.drops.get(hx)
+        if it is None or not self.in_reach(actor, hx): return
+        left = actor.inventory.add(it.typ, it.count)
+        if left == it.count: return
+        self.dispatch_event("on_do", None, ItemDropEvent(hx.state, Item.Stack(it.typ, left) if left else None), True)
+        self.dispatch_event("on_do", tid, InventoryChangeEvent(tid, actor.inventory.state), False)
+
+    # a worn item comes out of the inventory, and whatever it replaces goes back in or is dropped if there is no room
+    def try_equip_item(self, tid, evt):
+        actor = self.pcs.get(tid)
+        if actor is None or type(evt.typ) is not str or "slot" not in ITEMS.get(evt.typ, {}):
+            warning("{:} - rejected equip from {}: {}".format(LOGID.BAD_INPUT, tid, evt.typ))
+            return
+        if not actor.inventory.remove(evt.typ, 1): return
+        was = actor.equipment.equip(evt.typ)
+        if was is not None and actor.inventory.add(was, 1): self.drop_item(actor.hx, was, 1)
+        self.dispatch_event("on_do", None, EquipmentChangeEvent(tid, actor.equipment.state), True)
+        self.dispatch_event("on_do", tid, InventoryChangeEvent(tid, actor.inventory.state), False)
+
+    def try_unequip_item(self, tid, evt):
+        actor = self.pcs.get(tid)
+        if actor is None or type(evt.slot) is not str:
+            warning("{:} - rejected unequip from {}: {}".format(LOGID.BAD_INPUT, tid, evt.slot))
+            return
+        typ = actor.equipment.slots.get(evt.slot)
+        if typ is None or actor.inventory.add(typ, 1): return
+        actor.equipment.unequip(evt.slot)
+        self.dispatch_event("on_do", None, EquipmentChangeEvent(tid, actor.equipment.state), True)
+        self.dispatch_event("on_do", tid, InventoryChangeEvent(tid, actor.inventory.state), False)
+
+    # players have to be within TRADE_REACH to start a trade, after that it lasts until it is done or cancelled
+    def try_trade(self, tid, evt):
+        actor = self.pcs.get(tid)
+        if actor is None or type(evt.action) is not str or not(evt.target is None or type(evt.target) is int) \
+           or not(evt.typ is None or evt.typ in ITEMS) or not(evt.count is None or type(evt.count) is int and evt.count > 0):
+            warning("{:} - rejected trade from {}: {}".format(LOGID.BAD_INPUT, tid, evt))
+            return
+        trade = self.trades.get(tid)
+        if evt.action == Trade.REQUEST:
+            target = self.pcs.get(evt.target)
+            if trade is not None or target is None or evt.target == tid or evt.target in self.trades or target.hx.dist(actor.hx) > TRADE_REACH: return
+            trade = Trade.Trade(tid, evt.target)
+            self.trades[tid] = self.trades[evt.target] = trade
+        elif trade is None: return
+        elif evt.action == Trade.ACCEPT:
+            if trade.accepted or tid != trade.ids[1]: return
+            trade.accepted = True
+        elif evt.action == Trade.OFFER:
+            if evt.typ is None or evt.count is None or not trade.offer(tid, actor.inventory, evt.typ, evt.count): return
+            self.dispatch_event("on_do", tid, InventoryChangeEvent(tid, actor.inventory.state), False)
+        elif evt.action == Trade.CONFIRM:
+            if not trade.accepted: return
+            trade.confirmed.add(tid)
+            if len(trade.confirmed) == 2:
+                self.finish_trade(trade)
+                return
+        elif evt.action == Trade.CANCEL:
+            self.cancel_trade(trade)
+            return
+        else: return
+        self.tell_trade(trade, evt.action, tid, evt.typ, evt.count)
+
+    def tell_trade(self, trade, action, sender, typ=None, count=None):
+        for i in trade.ids: self.dispatch_event("on_do", i, TradeEvent(action, trade.other(sender), typ, count, sender), False)
+
+    # swapped only if both still have room, otherwise they have to make some and confirm again
+    def finish_trade(self, trade):
+        inventories = dict([(i, self.pcs[i].inventory) for i in trade.ids])
+        if not trade.fits(inventories):
+            trade.confirmed.clear()
+            self.tell_trade(trade, Trade.FULL, trade.ids[0])
+            return
+        trade.swap(inventories)
+        for i in trade.ids: self.trades.pop(i, None)
+        self.tell_trade(trade, Trade.DONE, trade.ids[0])
+        for i in trade.ids: self.dispatch_event("on_do", i, InventoryChangeEvent(i, inventories[i].state), False)
+
+    # everything offered goes back, and is dropped where its owner stands if there is no longer room for it
+    def cancel_trade(self, trade):
+        for i in trade.ids: self.trades.pop(i, None)
+        for i, typ, count in trade.cancel(dict([(i, self.pcs[i].inventory) for i in trade.ids])): self.drop_item(self.pcs[i].hx, typ, count)
+        self.tell_trade(trade, Trade.CANCEL, trade.ids[0])
+        for i in trade.ids: self.dispatch_event("on_do", i, InventoryChangeEvent(i, self.pcs[i].inventory.state), False)
+
+    def do_change_equipment(self, tid, evt):
+        actor = self.pcs.get(evt.id)
+        if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.id))
+        else: actor.equipment.state = evt.slots
+
+    def do_drop_item(self, tid, evt):
+        if evt.item is None: self.drops.pop(Hx(*evt.hx), None)
+        else: self.drops[Hx(*evt.hx)] = evt.item
+
+    # a solid tile can't be put where it would trap an actor
+    def occupied(self, hx, tile):
+        if tile is None or not(self.asset_factory._assets[tile.sprite__typ][tile.sprite__idx].flags & FLAG_SOLID): return False
+        for it in list(self.pcs.values()) + list(self.npcs.values()):
+            if Hx(it.hx.q, it.hx.r, 0) == Hx(hx.q, hx.r, 0) and it.hx.z < hx.z <= it.hx.z + it.height: return True
+        return False
     def do_change_tile(self, tid, evt):
         hxz = Hx(*evt.hx)
         tile = self.tiles.get(hxz)
@@ -140,11 +410,28 @@ class Impl(pyglet.event.EventDispatcher):
         if evt.tile is not None:
             self.tiles[hxz] = self.asset_factory.create_tile(evt.tile.sprite__typ, evt.tile.sprite__idx, hxz.into_px())
 
+    # an empty file means nothing has been saved yet, anything unreadable raises rather than being overwritten
     def from_file(self):
-        tiles = {}
         info("loading scene")
-        data = DECODER.loads(pyglet.resource.file("default.0","rb").read())
+        try:
+            data = pyglet.resource.file(MAP_FILE,"rb").read()
+        except pyglet.resource.ResourceNotFoundException: return None
+        if not data: return None
+        return self.from_state(DECODER.loads(data))
+
+    # written beside the old file then swapped in, so a crash mid-save leaves the last good scene
+    def to_file(self):
+        info("saving scene")
+        os.makedirs(MAP_PATH, exist_ok=True)
+        path = os.path.join(MAP_PATH, MAP_FILE)
+        with open(path+".tmp", "wb") as f: f.write(ENCODER.dumps(self.state))
+        os.replace(path+".tmp", path)
+
+    def from_state(self, data):
+        tiles = {}
+        if not isinstance(data, dict): raise ValueError("scene is not a dict of tiles")
         for i,it in data.items():
+            if not isinstance(it, Tile.State): raise ValueError("{} is not a tile".format(it))
             hx = Hx(*i)
             tile = self.asset_factory.create_tile(it.sprite__typ, it.sprite__idx, hx.into_px(), it.flags)
             tiles[hx] = tile
@@ -161,11 +448,46 @@ Impl.register_event_type('on_try')
 
 class Scene(Impl):
     def __init__(self, actor_factory, asset_factory, state_manager):
-        super().__init__(actor_factory, asset_factory, state_manager, None)
+        super().__init__(actor_factory, asset_factory, state_manager, None, random.Random())
+        self.explored = set()
+        self.explored_from = None
+        self.drop_tiles = {}
+
+    # columns the player has had line of sight to stay lit, everything else is dimmed
+    # only recalculated when the player changes hex or new tiles arrive
+    def explore(self, actor):
+        if actor.hx == self.explored_from: return
+        self.explored_from = actor.hx
+        self.explored |= set([(it.q,it.r) for it in self.visible_hexes(actor.hx+Hx(0,0,actor.height), VIEW_RADIUS)])
+        for i,it in list(self.tiles.items()):
+            if (i.q,i.r) in self.explored and it.sprite.color[:3] == UNEXPLORED_COLOR: it.sprite.color = (255,255,255)
+
+    # what a tile is drawn as when nothing is looking at it
+    def color(self, hx): return (255,255,255) if (hx.q,hx.r) in self.explored else UNEXPLORED_COLOR
+
+    def do_change_tile(self, tid, evt):
+        super().do_change_tile(tid, evt)
+        it = self.tiles.get(Hx(*evt.hx))
+        if it is not None: it.sprite.color = self.color(it.hx)
+        self.explored_from = None
+
+    def do_drop_item(self, tid, evt):
+        hx = Hx(*evt.hx)
+        it = self.drop_tiles.pop(hx, None)
+        if it is not None: it.delete()
+        super().do_drop_item(tid, evt)
+        if evt.item is not None:
+            it = self.asset_factory.create_tile(*ITEMS[evt.item.typ]["sprite"], hx.into_px())
+            it.sprite.scale = ITEM_SCALE
+            self.drop_tiles[hx] = it
+
+    def do_change_inventory(self, tid, evt):
+        actor = self.pcs.get(evt.id)
+        if actor is not None: actor.inventory.state = evt.items
 
     def do_load_actor(self, tid, evt):
         super().do_load_actor(tid, evt)
-        actors = self.pcs if evt.actor.typ == "blank" else self.npcs
+        actors = self.pcs if evt.actor.typ == PLAYER_TYPE else self.npcs
         actor = actors.get(evt.actor.id,None)
         if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.actor.id))
         elif evt.actor.id == self.state_manager.tid:
@@ -173,23 +495,26 @@ class Scene(Impl):
             self.state_manager.registry[ACTION_BAR].push_handlers(actor)
 
     def do_move_actor(self, tid, evt):
-        super().do_move_actor(tid, evt)
-        actors = self.pcs if evt.actor.typ == "blank" else self.npcs
+        actors = self.pcs if evt.actor.typ == PLAYER_TYPE else self.npcs
         actor = actors.get(evt.actor.id,None)
+        super().do_move_actor(tid, evt)
         if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.actor.id))
         else: actor.disp_dt += evt.dt
 
     def do_unload_actor(self, tid, evt):
-        actor = self.pcs.get(evt.id,None)
-        if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, evt.id))
-        else: actor.sprite.delete()
+        actors = self.pcs if evt.actor.typ == PLAYER_TYPE else self.npcs
+        actor = actors.get(evt.actor.id,None)
+        if actor is not None:
+            actor.sprite.delete()
+            it = self.tiles.get(actor.focus)
+            if it is not None and it.sprite is not None: it.sprite.color = self.color(it.hx)
         super().do_unload_actor(tid, evt)
 
     def on_looking_at(self, actor, now, was):
-        if self.tiles.get(was) is not None: self.tiles.get(was).sprite.color = (255,255,255)
+        if self.tiles.get(was) is not None: self.tiles.get(was).sprite.color = self.color(was)
         it = self.tiles.get(now+Hx(0,0,1))
         if it is None:
-            for i in range(R): 
+            for i in range(VIEW_RADIUS): 
                 it = self.tiles.get(now-Hx(0,0,i))
                 if it is not None: break
         if it is not None: 
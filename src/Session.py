@@ -7,11 +7,14 @@ from Quickle import ENCODER, DECODER
 
 OK = b'\x4f\x4b'
 
+# on the server everything received is from this session's tid, whatever the client put on the wire
 class Session():
-    def __init__(self, sock, incoming, outgoing):
+    def __init__(self, sock, incoming, outgoing, server=False):
         self.sock = sock
+        self.server = server
         self.outgoing = outgoing
         self.incoming = incoming
+        self.seq = -1
 
         self.do_exit = threading.Event()
         self.thread = threading.Thread(target=Session.sync, args=[self])
@@ -55,7 +58,7 @@ class Session():
                     except Exception as e:
                         debug(e)
                         continue
-                    if tid == None: tid = self.tid
+                    if self.server or tid == None: tid = self.tid
                     self.incoming.append((tid, evt, seq))
                 if it[i:i+2] == OK: 
                     rest = it[i+2:]
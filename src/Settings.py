@@ -0,0 +1,57 @@
+import json
+from logging import info, warning
+
+import Camera
+from LogId import LOGID
+
+DEFAULTS = dict(
+    camera = Camera.FOLLOW,
+    fullscreen = False,
+    zoom = 1.0,
+)
+
+# fields that only take one of a few values
+CHOICES = dict(
+    camera = [Camera.FOLLOW, Camera.FREE],
+)
+
+# client settings, missing or unreadable fields fall back to DEFAULTS so older files keep loading
+class Settings:
+    def __init__(self, path):
+        self.path = path
+        self.values = dict(DEFAULTS)
+        self.dirty = False
+        try:
+            with open(path) as f: data = json.load(f)
+        except FileNotFoundError:
+            info("no settings at {}, using defaults".format(path))
+            return
+        except (OSError, ValueError) as e:
+            warning("{:} - {}: {}".format(LOGID.BAD_SETTINGS, path, e))
+            return
+        if not isinstance(data, dict):
+            warning("{:} - {}: not an object".format(LOGID.BAD_SETTINGS, path))
+            return
+        for i,it in data.items():
+            if i not in DEFAULTS: continue
+            if type(it) is not type(DEFAULTS[i]) and not (type(DEFAULTS[i]) is float and type(it) is int):
+                warning("{:} - {}: {} should be {}".format(LOGID.BAD_SETTINGS, path, i, type(DEFAULTS[i]).__name__))
+                continue
+            if i in CHOICES and it not in CHOICES[i]:
+                warning("{:} - {}: {} should be one of {}".format(LOGID.BAD_SETTINGS, path, i, ", ".join(CHOICES[i])))
+                continue
+            self.values[i] = type(DEFAULTS[i])(it)
+
+    def __getitem__(self, key): return self.values[key]
+
+    def __setitem__(self, key, value):
+        if self.values[key] == value: return
+        self.values[key] = value
+        self.dirty = True
+
+    def save(self, dt=None):
+        if not self.dirty: return
+        try:
+            with open(self.path, "w") as f: json.dump(self.values, f, indent=2)
+            self.dirty = False
+        except OSError as e: warning("{:} - {}: {}".format(LOGID.BAD_SETTINGS, self.path, e))
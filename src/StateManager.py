@@ -1,9 +1,12 @@
 from collections import deque
-from logging import info, warning
+from logging import debug, info, warning
 import sys
+import time
 import pyglet
+from pyglet.window import key
 import Actor
 
+import Chat
 from Config import *
 from Event import *
 from HxPx import Hx, Px
@@ -13,23 +16,31 @@ from Quickle import ENCODER, DECODER
 SCENE = 'scene'
 OVERLAY = 'overlay'
 ACTION_BAR = 'action_bar'
+GRID = 'grid'
+CHAT = 'chat'
+OPTIONS = 'options'
 
 STATE_PLAY       = 1 << 0
 STATE_UI_OVERLAY = 1 << 1
+STATE_UI_CHAT    = 1 << 2
+STATE_UI_OPTIONS = 1 << 3
+STATE_PAUSE      = 1 << 4
 
 class Impl(pyglet.event.EventDispatcher):
     def __init__(self, actor_factory):
         self.state = 0
         self.seq = -1
+        self.seqs = {}
+        self.chats = {}
         self.registry = {}
         self.actor_factory = actor_factory
 
-    # Impl tries everything, and sends back what is done
+    # Impl tries everything, and sends back what is done along with the highest seq tried by tid
     def on_do(self, tid, evt, broadcast):
         self.dispatch_event("do_{}".format(evt.event), tid, evt)
-        self.dispatch_event("on_send", tid, evt, self.seq, broadcast)
+        self.dispatch_event("on_send", tid, evt, self.seqs.get(tid), broadcast)
     def on_try(self, tid, evt, seq):
-        self.seq = seq
+        if type(seq) is int and seq > self.seqs.get(tid, -1): self.seqs[tid] = seq
         self.dispatch_event("try_{}".format(evt.event), tid, evt)
 
     def try_init_connection(self, tid, evt):
@@ -41,13 +52,39 @@ class Impl(pyglet.event.EventDispatcher):
             self.dispatch_event("on_do", tid, TileChangeEvent(i.state, it.state), False)
         for i,it in list(self.registry[SCENE].npcs.items()) + list(self.registry[SCENE].pcs.items()): 
             self.dispatch_event("on_do", tid, ActorLoadEvent(it.state), False)
+            if it.equipment.slots: self.dispatch_event("on_do", tid, EquipmentChangeEvent(i, it.equipment.state), False)
+        for i,it in list(self.registry[SCENE].drops.items()):
+            self.dispatch_event("on_do", tid, ItemDropEvent(i.state, it), False)
         z = self.registry[SCENE].generator.elevation(Hx(0,0,0))
-        actor = self.actor_factory.create(tid, "blank", Px(0,0,z))
+        actor = self.actor_factory.create(tid, PLAYER_TYPE, Px(0,0,z))
         self.dispatch_event('on_do', tid, ActorLoadEvent(actor.state), True)
 
+    def try_chat(self, tid, evt):
+        pcs = self.registry[SCENE].pcs
+        sender = pcs.get(tid)
+        if sender is None or type(evt.text) is not str or type(evt.channel) is not str: return
+        if evt.target is not None and type(evt.target) is not int: return
+        text = "".join([c for c in evt.text if c.isprintable()]).strip()[:CHAT_MAX_LENGTH]
+        sent = self.chats.setdefault(tid, deque())
+        while sent and sent[0] < time.monotonic()-CHAT_RATE_PERIOD: sent.popleft()
+        if not text or len(sent) >= CHAT_RATE_LIMIT:
+            debug("dropped chat from {}".format(tid))
+            return
+        sent.append(time.monotonic())
+
+        if evt.channel == Chat.LOCAL: recipients = [i for i,it in pcs.items() if it.hx.dist(sender.hx) <= CHAT_LOCAL_RANGE]
+        elif evt.channel == Chat.WHISPER: recipients = [tid] + ([evt.target] if evt.target in pcs and evt.target != tid else [])
+        elif evt.channel == Chat.ZONE: recipients = list(pcs.keys())
+        else: return
+        evt = ChatEvent(evt.channel, text, tid, evt.target)
+        for i in recipients: self.dispatch_event("on_send", i, evt, None, False)
+
+    def do_unload_actor(self, tid, evt):
+        self.seqs.pop(evt.actor.id, None)
+        self.chats.pop(evt.actor.id, None)
+
     def on_close(self):
-        info("saving scene")
-        pyglet.resource.file("default.0","wb").write(ENCODER.dumps(self.registry[SCENE].state))
+        self.registry[SCENE].to_file()
         sys.exit(0)
 
     def begin(self):
@@ -58,13 +95,19 @@ class Impl(pyglet.event.EventDispatcher):
     def register(self, id, it):
         self.registry[id] = it
 
+Impl.register_event_type('do_change_equipment')
+Impl.register_event_type('do_change_inventory')
 Impl.register_event_type('do_change_tile')
+Impl.register_event_type('do_chat')
 Impl.register_event_type('do_discover_tile')
+Impl.register_event_type('do_drop_item')
 Impl.register_event_type('do_init_connection')
 Impl.register_event_type('do_load_actor')
 Impl.register_event_type('do_load_scene')
 Impl.register_event_type('do_move_actor')
 Impl.register_event_type('do_select_overlay')
+Impl.register_event_type('do_server_shutdown')
+Impl.register_event_type('do_trade')
 Impl.register_event_type('do_unload_actor')
 
 Impl.register_event_type('on_broadcast')
@@ -74,11 +117,17 @@ Impl.register_event_type('on_send')
 Impl.register_event_type('on_try')
 
 Impl.register_event_type('try_change_tile')
+Impl.register_event_type('try_chat')
 Impl.register_event_type('try_discover_tile')
+Impl.register_event_type('try_equip_item')
 Impl.register_event_type('try_init_connection')
 Impl.register_event_type('try_load_actor')
 Impl.register_event_type('try_load_scene')
 Impl.register_event_type('try_move_actor')
+Impl.register_event_type('try_pickup_item')
+Impl.register_event_type('try_trade')
+Impl.register_event_type('try_undo_tile')
+Impl.register_event_type('try_unequip_item')
 Impl.register_event_type('try_unload_actor')
 
 class StateManager(Impl):
@@ -92,15 +141,17 @@ class StateManager(Impl):
     # Client sends everything it tries to server, and does everything it is told to
     def on_do(self, tid, evt, broadcast, seq=None): 
         if seq is not None and tid == self.tid:
-            while self.evt_deque:
+            actor = self.registry[SCENE].pcs.get(self.tid)
+            was = actor.disp_pos if actor is not None else None
+            while self.evt_deque and self.evt_deque[0][0] <= seq:
                 i, it = self.evt_deque.popleft()
-                if i == seq: break
-                else: warning("{:} - skipping seq {}".format(LOGID.SKIP_SEQ , i))
-            evt.dt = 0
+                if i != seq: warning("{:} - skipping seq {}".format(LOGID.SKIP_SEQ , i))
+            if isinstance(evt,ActorMoveEvent): evt.dt = 0
             self.dispatch_event("do_{}".format(evt.event), tid, evt)
             for i,it in list(self.evt_deque):
                 if isinstance(it,ActorMoveEvent): it.dt = 0
                 self.dispatch_event("do_{}".format(it.event), tid, it)
+            if actor is not None: actor.correct(was)
         else: self.dispatch_event("do_{}".format(evt.event), tid, evt)
 
     def on_try(self, tid, evt, sync):
@@ -116,8 +167,20 @@ class StateManager(Impl):
         self.tid = evt.tid
         self.dispatch_event('on_try', self.tid, SceneLoadEvent(), True)
 
+    def do_server_shutdown(self, tid, evt):
+        warning("server is shutting down")
+        pyglet.clock.schedule_once(lambda dt: self.window.close(), SHUTDOWN_GRACE)
+
+    def on_key_press(self, sym, mod):
+        if sym == key.F3: self.registry[GRID].toggle()
+        if sym == key.ENTER: self.on_chat()
+        if sym == key.P: self.on_pause()
+        if sym == key.O: self.on_options()
+        if sym == key.F: self.on_pickup()
+        if DEV and sym == key.Z and mod & key.MOD_CTRL: self.dispatch_event('on_try', None, TileUndoEvent(mod & key.MOD_SHIFT != 0), True)
+
     def on_close(self, *args):
-        if(self.state & STATE_UI_OVERLAY):
+        if(self.state & (STATE_UI_OVERLAY | STATE_UI_CHAT | STATE_UI_OPTIONS)):
             self.window.pop_handlers()
             self.window.push_handlers(self.key_state_handler)
             self.window.push_handlers(self.registry[ACTION_BAR])
@@ -131,6 +194,34 @@ class StateManager(Impl):
             self.state |= STATE_UI_OVERLAY
             self.dispatch_event("on_open",*args)
 
+    def on_chat(self):
+        if(self.state & STATE_PLAY):
+            self.window.pop_handlers()
+            self.window.pop_handlers()
+            self.window.push_handlers(self.registry[CHAT])
+            self.state |= STATE_UI_CHAT
+            self.registry[CHAT].visible = True
+
+    # the nearest drop in reach, the server decides if it can really be picked up
+    def on_pickup(self):
+        actor = self.registry[SCENE].pcs.get(self.tid)
+        if not(self.state & STATE_PLAY) or actor is None: return
+        drops = [i for i in self.registry[SCENE].drops.keys() if self.registry[SCENE].in_reach(actor, i)]
+        if drops: self.dispatch_event('on_try', self.tid, ItemPickupEvent(min(drops, key=lambda it: it.dist(actor.hx)).state), True)
+
+    def on_options(self):
+        if(self.state & STATE_PLAY):
+            self.window.pop_handlers()
+            self.window.pop_handlers()
+            self.window.push_handlers(self.registry[OPTIONS])
+            self.state |= STATE_UI_OPTIONS
+            self.registry[OPTIONS].visible = True
+
+    # paused the local actor takes no input, the world carries on and is still drawn
+    def on_pause(self):
+        if(self.state == STATE_PLAY): self.state = STATE_PAUSE
+        elif(self.state == STATE_PAUSE): self.state = STATE_PLAY
+
     def begin(self):
         super().begin()
         self.window.push_handlers(self)
@@ -139,6 +230,10 @@ class StateManager(Impl):
         self.push_handlers(self.registry[OVERLAY])
         self.registry[OVERLAY].push_handlers(self)
         self.registry[OVERLAY].push_handlers(self.registry[SCENE])    
+        self.push_handlers(self.registry[GRID])
+        self.push_handlers(self.registry[CHAT])
+        self.registry[CHAT].push_handlers(self)
+        self.registry[OPTIONS].push_handlers(self)
         self.dispatch_event('on_try', None, ConnectionInitEvent(None), True)
 
 StateManager.register_event_type('on_open')
@@ -0,0 +1,51 @@
+import Item
+
+REQUEST = "request"
+ACCEPT = "accept"
+OFFER = "offer"
+CONFIRM = "confirm"
+CANCEL = "cancel"
+DONE = "done"
+FULL = "full"
+
+# two players swapping items, anything offered is held here out of its owner's inventory
+# until both confirm and it is swapped, or the trade is cancelled and it is given back
+class Trade:
+    def __init__(self, a, b):
+        self.ids = (a, b)
+        self.accepted = False
+        self.escrow = {a: Item.Inventory(), b: Item.Inventory()}
+        self.confirmed = set()
+
+    def other(self, id): return self.ids[1] if id == self.ids[0] else self.ids[0]
+
+    # any change to what is offered has to be confirmed again by both
+    def offer(self, id, inventory, typ, count):
+        if not self.accepted or not inventory.remove(typ, count): return False
+        left = self.escrow[id].add(typ, count)
+        if left: inventory.add(typ, left)
+        self.confirmed.clear()
+        return left < count
+
+    # each side has room for everything the other offered
+    def fits(self, inventories):
+        for id in self.ids:
+            it = Item.Inventory()
+            it.state = inventories[id].state
+            if any(it.add(jt.typ, jt.count) for jt in self.escrow[self.other(id)].items): return False
+        return True
+
+    def swap(self, inventories):
+        for id in self.ids:
+            for it in self.escrow[self.other(id)].items: inventories[id].add(it.typ, it.count)
+            self.escrow[self.other(id)] = Item.Inventory()
+
+    # gives back everything offered, returning what no longer fits as (id, typ, count)
+    def cancel(self, inventories):
+        spilt = []
+        for id in self.ids:
+            for it in self.escrow[id].items:
+                left = inventories[id].add(it.typ, it.count)
+                if left: spilt.append((id, it.typ, left))
+            self.escrow[id] = Item.Inventory()
+        return spilt
@@ -7,14 +7,18 @@ from pyglet.window import key
 import sys
 
 from ActionBar import ActionBar
+from Chat import Chat
 import Actor
 import Asset
-from Camera import Camera, CenteredCamera
+import Camera
+from Camera import CenteredCamera
 from Config import *
-from HxPx import Px
+from Grid import Grid
 from LogId import LOGID
+from Options import Options
 from Scene.Scene import Scene
 from Session import Session
+from Settings import Settings
 import StateManager
 from Overlay import Overlay
 
@@ -26,7 +30,9 @@ logging.basicConfig(stream=sys.stderr,
 pyglet.resource.path = ['../assets/sprites']
 pyglet.resource.reindex()
 
-window = pyglet.window.Window(fullscreen=False, resizable=True)
+settings = Settings(SETTINGS_FILE)
+
+window = pyglet.window.Window(fullscreen=settings["fullscreen"], resizable=True)
 
 sock = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
 sock.connect((SERVER,SERVER_PORT))
@@ -38,33 +44,56 @@ asset_factory = Asset.Factory(batch)
 actor_factory = Actor.Factory(key_state_handler, asset_factory)
 
 camera = CenteredCamera(window)
+camera.zoom = settings["zoom"]
 state_manager = StateManager.StateManager(window, key_state_handler, asset_factory)
 state_manager.push_handlers(session)
 scene = Scene(actor_factory, asset_factory, state_manager)
 
 overlay = Overlay(asset_factory)
 
-camera_ui = Camera(window)
+batch_debug = pyglet.graphics.Batch()
+grid = Grid(scene, batch_debug)
+
+camera_ui = Camera.Camera(window)
 batch_ui = pyglet.graphics.Batch()
 fps = pyglet.window.FPSDisplay(window=window)
+paused = pyglet.text.Label("PAUSED", font_size=24, bold=True, anchor_x='center', anchor_y='center')
 action_bar = ActionBar(window, scene, batch_ui)
+chat = Chat(batch_ui)
+options = Options(settings, window, camera, batch_ui)
 
 state_manager.register(StateManager.SCENE, scene)
 state_manager.register(StateManager.OVERLAY, overlay)
 state_manager.register(StateManager.ACTION_BAR, action_bar)
+state_manager.register(StateManager.GRID, grid)
+state_manager.register(StateManager.CHAT, chat)
+state_manager.register(StateManager.OPTIONS, options)
 
 @window.event
 def on_draw():
     window.clear()
     with camera:
         batch.draw()
+        batch_debug.draw()
     with camera_ui:
         batch_ui.draw()
         fps.draw()
+        if state_manager.state & StateManager.STATE_PAUSE:
+            paused.position = (window.width/2, window.height/2, 0)
+            paused.draw()
+
+@window.event
+def on_key_press(sym, mod):
+    if sym == key.V: settings["camera"] = Camera.FREE if settings["camera"] == Camera.FOLLOW else Camera.FOLLOW
+
+@window.event
+def on_mouse_drag(x, y, dx, dy, buttons, mod):
+    if settings["camera"] == Camera.FREE: camera.move(-dx/camera.zoom, -dy/camera.zoom)
 
 def on_update(dt):
     if key_state_handler[key.MINUS] or key_state_handler[key.NUM_SUBTRACT]: camera.zoom -= .1
     if key_state_handler[key.EQUAL] or key_state_handler[key.NUM_ADD]: camera.zoom += .1
+    settings["zoom"] = camera.zoom
 
     for tid, evt, seq in session.recv():
         state_manager.dispatch_event("on_do", tid, evt, None, seq)
@@ -72,17 +101,17 @@ def on_update(dt):
         actor = state_manager.registry[StateManager.SCENE].pcs.get(state_manager.tid)
         if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, state_manager.tid))
         else:
-            actor.update(actor.state, dt)
-            camera.position = actor.px.into_screen((0,18,0))[:2]
+            if state_manager.state & StateManager.STATE_PLAY: actor.update(actor.state, dt)
+            if settings["camera"] == Camera.FOLLOW: camera.position = actor.px.into_screen((0,18,0))[:2]
+            grid.update(actor.hx)
+            scene.explore(actor)
     for i,it in list(state_manager.registry[StateManager.SCENE].pcs.items()) + list(state_manager.registry[StateManager.SCENE].npcs.items()):
-        if it.disp_dt > 0:
-            pos = Px(*(it.px.into_screen((0, it.air_dz*TILE_RISE, 1+it.height+it.air_dz))))
-            it.disp_pos = it.disp_pos.lerp(pos, min(1, dt/it.disp_dt))
-            it.disp_dt = max(0, it.disp_dt-dt)
-        it.recalc()
+        it.interpolate(dt)
 
 pyglet.clock.schedule_interval(on_update, 1/120.0)
+pyglet.clock.schedule_interval(settings.save, 1)
 
 if __name__ == "__main__": 
     state_manager.begin()
     pyglet.app.run()
+    settings.save()
@@ -1,11 +1,15 @@
+import argparse
 from collections import deque
 import logging
-from logging import debug, info, warning
+from logging import error, info, warning
+import os
 import signal
 import pyglet
+import random
 import socket
 import sys
 import threading
+import time
 
 import Actor
 import Asset
@@ -14,18 +18,22 @@ from Config import *
 from Event import *
 from HxPx import Hx
 from LogId import LOGID
+from Metrics import Metrics
+import Replay
 import Scene.Generator
 import Scene.Scene
 from Session import OK, Session
 import StateManager
 
-R = Scene.Scene.R*3
-
 class Server(pyglet.event.EventDispatcher):
     def __init__(self):
         self.incoming = deque()
         self.sessions = {}
+        self.recorder = None
+        self.metrics = None
+        self.replay = None
 
+    def listen(self):
         self.sock = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
         self.sock.bind(("0.0.0.0",SERVER_PORT))
         self.sock.listen(5)
@@ -36,19 +44,30 @@ class Server(pyglet.event.EventDispatcher):
             sock, addr = self.sock.accept()
             info("accepted from {}".format(addr))
             sock.send(OK)
-            session = Session(sock, self.incoming, deque())
+            session = Session(sock, self.incoming, deque(), True)
             self.sessions[session.tid] = session
     
     def on_send(self, tid, evt, seq, broadcast):
+        if self.metrics is not None: self.metrics.sent += len(self.sessions) if broadcast else 1
         if broadcast:
             for i,it in self.sessions.items():
                 it.on_send(tid, evt, seq if it.tid == tid else None)
-        else: self.sessions[tid].on_send(tid, evt, seq)
+        elif tid in self.sessions: self.sessions[tid].on_send(tid, evt, seq)
 
     def update(self, dt):
         while self.incoming:
             tid, evt, seq = self.incoming.popleft()
+            session = self.sessions.get(tid)
+            if seq is not None and self.replay is None:
+                if session is None: continue # a client that has already gone
+                if type(seq) is not int or seq <= session.seq:
+                    warning("{:} - out of order seq {} from {}".format(LOGID.BAD_INPUT, seq, tid))
+                    continue
+                session.seq = seq
+            if self.recorder is not None: self.recorder.record(tid, evt, seq)
+            if self.metrics is not None: self.metrics.received += 1
             self.dispatch_event("on_try", tid, evt, seq)
+        if self.recorder is not None: self.recorder.tick(dt)
 
 Server.register_event_type("on_try")
 
@@ -57,36 +76,82 @@ logging.basicConfig(stream=sys.stderr,
                     format='%(levelname)-5s %(asctime)s %(module)s:%(funcName)s %(message)s',
                     datefmt="%Y-%m-%dT%H:%M:%S")
 
-pyglet.resource.path = ['../assets/sprites','../data/maps']
+pyglet.resource.path = ['../assets/sprites',MAP_PATH]
 pyglet.resource.reindex()
 
+parser = argparse.ArgumentParser()
+parser.add_argument("--record", help="record every incoming event to a replay file")
+parser.add_argument("--replay", help="headless playback of a replay file")
+parser.add_argument("--metrics", help="append a json line of server health to a file every METRICS_PERIOD seconds")
+args = parser.parse_args()
+
+replay = Replay.Player(args.replay) if args.replay else None
 server = Server()
-thread = threading.Thread(target=Server.accept, args=[server])
-thread.daemon = True
-thread.start()
+server.replay = replay
+if replay is None:
+    server.listen()
+    thread = threading.Thread(target=Server.accept, args=[server])
+    thread.daemon = True
+    thread.start()
 
-behaviour_factory = Behaviour.Factory()
+rng = random.Random(SEED)
+behaviour_factory = Behaviour.Factory(rng)
 actor_factory = Actor.ImplFactory(behaviour_factory)
 state_manager = StateManager.Impl(actor_factory)
 server.push_handlers(state_manager)
 state_manager.push_handlers(server)
-scene = Scene.Scene.Impl(actor_factory, Asset.Factory(None), state_manager, Scene.Generator.Impl(42)) # TODO magic number
+scene = Scene.Scene.Impl(actor_factory, Asset.Factory(None), state_manager, Scene.Generator.Impl(SEED), rng)
 state_manager.register(StateManager.SCENE, scene)
 state_manager.begin()
 
-try:
-    scene.tiles = scene.from_file()
-except Exception as e:
-    debug(e)
-    state_manager.dispatch_event("on_try", None, TileDiscoverEvent((0,0,0)), None)
+if replay is not None: replay.begin(rng, scene)
+else:
+    try:
+        tiles = scene.from_file()
+    except Exception as e:
+        error("{:} - {}: {}".format(LOGID.BAD_MAP, MAP_FILE, e))
+        sys.exit(1)
+    if tiles is not None: scene.tiles = tiles
+    else:
+        state_manager.dispatch_event("on_try", None, TileDiscoverEvent((0,0,0)), None)
+        scene.to_file()
+
+if args.record:
+    server.recorder = Replay.Recorder(args.record, rng, scene)
+    state_manager.push_handlers(on_close=lambda: server.recorder.close(scene))
+
+if args.metrics:
+    server.metrics = Metrics(args.metrics)
+    state_manager.push_handlers(on_close=lambda: server.metrics.close())
+    pyglet.clock.schedule_interval(lambda dt: server.metrics.sample(dt, server.sessions, scene), METRICS_PERIOD)
 
+TICK = 1/SERVER_TICK_RATE
+accumulator = 0
+
+# real time is accumulated and the simulation always steps in whole ticks
 def on_update(dt):
+    global accumulator
+    accumulator += dt
+    if accumulator > SERVER_MAX_TICKS_PER_UPDATE*TICK:
+        warning("dropping {:.3f}s, server is behind".format(accumulator - SERVER_MAX_TICKS_PER_UPDATE*TICK))
+        accumulator = SERVER_MAX_TICKS_PER_UPDATE*TICK
+    while accumulator >= TICK and not stopping:
+        accumulator -= TICK
+        start = time.perf_counter()
+        tick(TICK)
+        if server.metrics is not None: server.metrics.tick(time.perf_counter()-start)
+    if stopping: stop()
+
+def tick(dt):
+    if replay is not None:
+        dt = replay.next(server.incoming)
+        if dt is None: sys.exit(0 if replay.verify(scene) else 1)
     server.update(dt)
     for i,it in server.sessions.items():
         if it.do_exit.is_set():
             actor = state_manager.registry[StateManager.SCENE].pcs.get(i,None)
             if actor is None: warning("{:} - Actor not found: {}".format(LOGID.NF_ACTOR, i))
-            else: state_manager.dispatch_event('on_do', None, ActorUnloadEvent(actor.state), True)
+            else: server.incoming.append((None, ActorUnloadEvent(actor.state), None))
             it.sock.close()
             del server.sessions[i]
             break
@@ -95,15 +160,34 @@ def on_update(dt):
     for i,it in list(scene.pcs.items()): 
         it.update(it.state,dt)
         for j,jt in list(inactive):
-            if it.hx.dist(jt.hx) < R: 
+            if it.hx.dist(jt.hx) < ACTIVE_RADIUS: 
                 inactive.remove((j,jt))
                 active.append((j,jt))
     for i,it in active:
         it.update(it.state,dt)
-    if dt > 0.06: warning("dt > 0.06: {}".format(dt))
         
-pyglet.clock.schedule_interval(on_update, 1/20.0)
+pyglet.clock.schedule_interval(on_update, TICK)
+
+# a signal only asks to stop so the current tick finishes first, a second signal gives up without saving
+stopping = False
+def shutdown(sig, frame):
+    global stopping
+    if stopping:
+        warning("forced exit, scene not saved")
+        os._exit(1)
+    stopping = True
+
+# clients are told before the scene is saved
+def stop():
+    info("shutting down")
+    server.on_send(None, ServerShutdownEvent(), None, True)
+    deadline = time.monotonic()+SHUTDOWN_GRACE
+    while any(it.outgoing for it in list(server.sessions.values())) and time.monotonic() < deadline: time.sleep(.05)
+    state_manager.dispatch_event('on_close')
 
 if __name__ == "__main__": 
-    signal.signal(signal.SIGINT, lambda sig,frame: state_manager.dispatch_event('on_close'))
+    if replay is not None:
+        while True: tick(None)
+    signal.signal(signal.SIGINT, shutdown)
+    signal.signal(signal.SIGTERM, shutdown)
     pyglet.app.run()
@@ -0,0 +1,190 @@
+import json
+import math
+import os
+import random
+import tempfile
+import unittest
+
+import Actor
+import Camera
+from Config import *
+from Event import *
+from HxPx import DIRECTIONS, Hx, Px
+import Item
+import Scene.Scene
+from Settings import Settings
+import StateManager
+import Tile
+import Trade
+
+# run from src with `python -m unittest tests`
+
+class TestHx(unittest.TestCase):
+    def test_line_to_same_tile(self):
+        self.assertEqual(Hx(2,-1,3).line_to(Hx(2,-1,3)), [Hx(2,-1,3)])
+
+    def test_line_to_includes_both_ends(self):
+        for a,b in [(Hx(0,0,0), Hx(5,0,0)), (Hx(0,0,0), Hx(3,-5,2)), (Hx(-2,4,0), Hx(1,1,-1))]:
+            line = a.line_to(b)
+            self.assertEqual(line[0], a)
+            self.assertEqual(line[-1], b)
+            self.assertEqual(len(line), a.dist(b)+1)
+            for i in range(len(line)-1): self.assertEqual(line[i].dist(line[i+1]), 1)
+
+    def test_line_to_is_symmetric(self):
+        rng = random.Random(0)
+        for _ in range(100):
+            a = Hx(rng.randint(-9,9), rng.randint(-9,9), rng.randint(-3,3))
+            b = Hx(rng.randint(-9,9), rng.randint(-9,9), rng.randint(-3,3))
+            self.assertEqual(a.line_to(b), list(reversed(b.line_to(a))))
+
+    def test_ring(self):
+        self.assertEqual(Hx(1,2,3).ring(0), [Hx(1,2,3)])
+        for n in range(1,6):
+            ring = Hx(1,2,3).ring(n)
+            self.assertEqual(len(ring), 6*n)
+            self.assertEqual(len(set(ring)), 6*n)
+            for it in ring: self.assertEqual(it.dist(Hx(1,2,3)), n)
+
+    def test_spiral(self):
+        self.assertEqual(len(Hx(0,0,0).spiral(4)), 1+3*4*5)
+        self.assertEqual(Hx(0,0,0).spiral(1)[0], Hx(0,0,0))
+
+    def test_neighbors(self):
+        self.assertEqual(set(Hx(1,1,2).neighbors()), set(Hx(1,1,2).ring(1)))
+
+    def test_nearest_direction(self):
+        self.assertEqual(Hx(0,0,0).nearest_direction(), Hx(0,0,0))
+        for it in DIRECTIONS:
+            self.assertEqual(it.nearest_direction(), it)
+            self.assertEqual(Hx(it.q*7, it.r*7, 0).nearest_direction(), it)
+        self.assertEqual(Hx(5,-1,0).nearest_direction(), Hx(1,0,0))
+        self.assertEqual(Hx(-1,-20,0).nearest_direction(), Hx(0,-1,0))
+        self.assertIn(Hx(1,1,0).nearest_direction(), DIRECTIONS)
+
+    def test_within(self):
+        self.assertTrue(Hx(0,0,0).within(Hx(1,0,2), 1, 2))
+        self.assertFalse(Hx(0,0,0).within(Hx(1,0,3), 1, 2))
+        self.assertFalse(Hx(0,0,0).within(Hx(2,0,0), 1, 2))
+
+class TestItem(unittest.TestCase):
+    def test_add_fills_stacks_then_overflows(self):
+        it = Item.Inventory()
+        self.assertEqual(it.add("wood", ITEMS["wood"]["stack"]+5), 0)
+        self.assertEqual([jt.count for jt in it.items], [ITEMS["wood"]["stack"], 5])
+        self.assertEqual(it.add("wood", 2), 0)
+        self.assertEqual(it.items[1].count, 7)
+        it.add("stone", ITEMS["stone"]["stack"]*(INVENTORY_SIZE-2))
+        self.assertEqual(len(it.items), INVENTORY_SIZE)
+        self.assertEqual(it.add("wood", ITEMS["wood"]["stack"]), 7)
+
+    def test_remove(self):
+        it = Item.Inventory()
+        it.add("wood", 5)
+        self.assertFalse(it.remove("wood", 6))
+        self.assertEqual(it.count("wood"), 5)
+        self.assertTrue(it.remove("wood", 5))
+        self.assertEqual(it.items, [])
+
+    def test_equipment_reverts_exactly(self):
+        actor = Actor.Impl(1, PLAYER_TYPE, Px(0,0,0), None)
+        speed, vertical = actor.speed, actor.vertical
+        actor.equipment.equip("wood")
+        self.assertEqual(actor.vertical, vertical + ITEMS["wood"]["mods"]["vertical"])
+        actor.equipment.equip("stone")
+        self.assertEqual(actor.speed, speed + ITEMS["stone"]["mods"]["speed"])
+        actor.equipment.unequip(ITEMS["wood"]["slot"])
+        actor.equipment.unequip(ITEMS["stone"]["slot"])
+        self.assertEqual((actor.speed, actor.vertical), (speed, vertical))
+
+class TestSettings(unittest.TestCase):
+    def load(self, data):
+        with tempfile.NamedTemporaryFile("w", suffix=".json", delete=False) as f: json.dump(data, f)
+        try: return Settings(f.name)
+        finally: os.remove(f.name)
+
+    def test_missing_fields_use_defaults(self):
+        settings = self.load(dict(zoom=2))
+        self.assertEqual(settings["zoom"], 2.0)
+        self.assertEqual(settings["camera"], Camera.FOLLOW)
+        self.assertEqual(settings["fullscreen"], False)
+
+    def test_bad_fields_use_defaults(self):
+        settings = self.load(dict(camera="folow", fullscreen="yes", unknown=1))
+        self.assertEqual(settings["camera"], Camera.FOLLOW)
+        self.assertEqual(settings["fullscreen"], False)
+
+    def test_missing_file_uses_defaults(self):
+        self.assertEqual(Settings(os.path.join(tempfile.gettempdir(), "missing-settings.json"))["camera"], Camera.FOLLOW)
+
+# a flat floor of tiles around the origin with players standing on it
+class SceneTestCase(unittest.TestCase):
+    def setUp(self):
+        self.scene = Scene.Scene.Impl(None, None, StateManager.Impl(None), None, random.Random(0))
+        for hx in Hx(0,0,0).spiral(4): self.scene.tiles[hx] = Tile.Tile(hx.into_px(), None, FLAG_SOLID)
+        self.done = []
+        self.scene.push_handlers(on_do=lambda tid, evt, broadcast: self.done.append((tid, evt)))
+
+    def add_pc(self, id, hx=Hx(0,0,0)):
+        self.scene.pcs[id] = Actor.Impl(id, PLAYER_TYPE, hx.into_px(), None)
+        return self.scene.pcs[id]
+
+    def move(self, actor, heading, dt, jump=False):
+        state = actor.state
+        state.heading = heading.state
+        if jump: state.air_time = 0
+        self.scene.try_move_actor(actor.id, ActorMoveEvent(state, dt))
+        return [evt for tid, evt in self.done if isinstance(evt, ActorMoveEvent)][-1]
+
+class TestMove(SceneTestCase):
+    def test_dt_is_clamped(self):
+        evt = self.move(self.add_pc(1), Hx(1,0,0), 10)
+        self.assertEqual(evt.dt, MAX_MOVE_DT)
+        self.assertAlmostEqual(math.dist(evt.actor.px[:2], (0,0)), DEFAULT_SPEED*MAX_MOVE_DT)
+
+    def test_no_heading_stays_put(self):
+        actor = self.add_pc(1)
+        for jump in [False, True]:
+            evt = self.move(actor, Hx(0,0,0), MAX_MOVE_DT, jump)
+            self.assertTrue(all(math.isfinite(it) for it in evt.actor.px))
+            self.assertEqual(evt.actor.px[:2], (0,0))
+
+    def test_ledges_up_to_mantle_height(self):
+        for height in range(1, MANTLE_HEIGHT+3):
+            for z in range(1, height+1): self.scene.tiles[Hx(1,0,z)] = Tile.Tile(Hx(1,0,z).into_px(), None, FLAG_SOLID)
+            self.assertEqual(self.scene.ledge(Hx(0,0,0), Hx(1,0,0), DEFAULT_HEIGHT), height if height <= MANTLE_HEIGHT else None)
+
+class TestTrade(SceneTestCase):
+    def setUp(self):
+        super().setUp()
+        self.a, self.b = self.add_pc(1), self.add_pc(2, Hx(1,0,0))
+        self.a.inventory.add("wood", 5)
+        self.b.inventory.add("stone", 3)
+        self.scene.try_trade(1, TradeEvent(Trade.REQUEST, 2))
+        self.scene.try_trade(2, TradeEvent(Trade.ACCEPT))
+        self.scene.try_trade(1, TradeEvent(Trade.OFFER, None, "wood", 4))
+        self.scene.try_trade(2, TradeEvent(Trade.OFFER, None, "stone", 3))
+
+    def test_swaps_once_both_confirm(self):
+        self.assertEqual((self.a.inventory.count("wood"), self.b.inventory.count("stone")), (1, 0))
+        self.scene.try_trade(1, TradeEvent(Trade.CONFIRM))
+        self.assertEqual(self.b.inventory.count("wood"), 0)
+        self.scene.try_trade(2, TradeEvent(Trade.CONFIRM))
+        self.assertEqual((self.a.inventory.count("wood"), self.a.inventory.count("stone")), (1, 3))
+        self.assertEqual((self.b.inventory.count("wood"), self.b.inventory.count("stone")), (4, 0))
+        self.assertEqual(self.scene.trades, {})
+
+    def test_changing_an_offer_needs_confirming_again(self):
+        self.scene.try_trade(1, TradeEvent(Trade.CONFIRM))
+        self.scene.try_trade(1, TradeEvent(Trade.OFFER, None, "wood", 1))
+        self.scene.try_trade(2, TradeEvent(Trade.CONFIRM))
+        self.assertEqual(self.b.inventory.count("wood"), 0)
+
+    def test_leaving_gives_everything_back(self):
+        self.scene.try_trade(1, TradeEvent(Trade.CONFIRM))
+        self.scene.do_unload_actor(None, ActorUnloadEvent(self.b.state))
+        self.assertEqual(self.a.inventory.count("wood"), 5)
+        self.assertEqual(self.b.inventory.count("stone"), 3)
+        self.assertEqual(self.scene.trades, {})
+
+if __name__ == "__main__": unittest.main()